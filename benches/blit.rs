@@ -0,0 +1,77 @@
+//! Compares the row-wise blitter with the per-pixel loop `Sprite::draw` used to have, and tinting a row at a time
+//! with copying it.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+#[allow(dead_code)]
+#[path = "../src/blit.rs"]
+mod blit;
+
+use blit::{blend, blit, DrawMode, View};
+
+const FRAME_WIDTH: usize = 256;
+const FRAME_HEIGHT: usize = 240;
+const SPRITE_SIZE: usize = 10;
+
+/// The old blitter: walks every pixel and only bounds-checks the flat frame index
+fn blit_per_pixel(frame: &mut [u8], view: View, anchor_x: i32, anchor_y: i32) {
+    for y in 0..view.height {
+        for x in 0..view.width {
+            let src = ((view.y + y) * view.stride + view.x + x) * 4;
+            let pixel_index = ((y as i32 + anchor_y) * FRAME_WIDTH as i32 + x as i32 + anchor_x) * 4;
+            if pixel_index + 4 <= frame.len() as i32 && pixel_index >= 0 {
+                let pixel_index = pixel_index as usize;
+                blend(&mut frame[pixel_index..pixel_index + 4], &view.pixels[src..src + 4]);
+            }
+        }
+    }
+}
+
+/// Draw a full well's worth of blocks, 10 by 20
+fn draw_board(c: &mut Criterion) {
+    let pixels = [200, 40, 40, 255].repeat(SPRITE_SIZE * SPRITE_SIZE);
+    let view = View {
+        pixels: &pixels,
+        stride: SPRITE_SIZE,
+        x: 0,
+        y: 0,
+        width: SPRITE_SIZE,
+        height: SPRITE_SIZE,
+    };
+    let mut frame = vec![0; FRAME_WIDTH * FRAME_HEIGHT * 4];
+    let cells = || (0..20).flat_map(|row| (0..10).map(move |column| (78 + column * 10, 20 + row * 10)));
+
+    let mut group = c.benchmark_group("board of 10x10 blocks");
+    group.bench_function("per pixel", |b| {
+        b.iter(|| {
+            for (x, y) in cells() {
+                blit_per_pixel(black_box(&mut frame), view, x, y);
+            }
+        })
+    });
+    group.bench_function("row copy", |b| {
+        b.iter(|| {
+            for (x, y) in cells() {
+                blit(black_box(&mut frame), FRAME_WIDTH, view, (x, y), &DrawMode::Copy);
+            }
+        })
+    });
+    group.bench_function("row blend", |b| {
+        b.iter(|| {
+            for (x, y) in cells() {
+                blit(black_box(&mut frame), FRAME_WIDTH, view, (x, y), &DrawMode::Alpha);
+            }
+        })
+    });
+    group.bench_function("row tint", |b| {
+        b.iter(|| {
+            for (x, y) in cells() {
+                blit(black_box(&mut frame), FRAME_WIDTH, view, (x, y), &DrawMode::Tint([255, 128, 64, 255]));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, draw_board);
+criterion_main!(benches);
@@ -0,0 +1,281 @@
+//! Sprite sheets: PNG images from the `assets/` directory, sliced into named regions.
+//!
+//! A sheet is sliced either into a grid of equal cells, named by their index, or by a manifest file:
+//!
+//! ```text
+//! image: blocks.png
+//! # name = x y width height
+//! sprite: dirt = 10 0 10 10
+//! # or a whole grid at once, named <prefix><index>
+//! grid: cell = 10 10
+//! ```
+
+use log::warn;
+use png::{ColorType, Transformations};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Directory the sprite sheets live in
+pub const ASSET_DIR: &str = "assets";
+
+/// An RGBA image, row-major with 4 bytes per pixel
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// An image of a single colour
+    pub fn filled(width: usize, height: usize, color: [u8; 4]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: color.repeat(width * height),
+        }
+    }
+
+    /// Decode a PNG file, converting any colour type to RGBA
+    pub fn load_png(path: &Path) -> Result<Self, AtlasError> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let data = &buffer[..info.buffer_size()];
+
+        let pixels = match info.color_type {
+            ColorType::Rgba => data.to_vec(),
+            ColorType::Rgb => data.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+            ColorType::GrayscaleAlpha => data.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+            ColorType::Grayscale => data.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            // Expanded to RGB or RGBA by the transformations
+            ColorType::Indexed => unreachable!("indexed PNGs are expanded when decoded"),
+        };
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    /// The RGBA pixel at `(x, y)`
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// True if `region` lies entirely inside the image
+    pub fn contains(&self, region: Region) -> bool {
+        let fits = |start: usize, length: usize, size: usize| start.checked_add(length).is_some_and(|end| end <= size);
+        fits(region.x, region.width, self.width) && fits(region.y, region.height, self.height)
+    }
+}
+
+/// A rectangle of an image, in pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// A manifest or image could not be read
+    Io(io::Error),
+    /// The image is not a PNG we can decode
+    Png(png::DecodingError),
+    /// A manifest line that can't be understood, with its 1-based line number
+    Manifest { line: usize, message: String },
+    /// A sprite that reaches past the edge of the sheet
+    OutOfBounds { name: String, region: Region },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::Io(err) => write!(f, "could not read the file: {err}"),
+            AtlasError::Png(err) => write!(f, "could not decode the image: {err}"),
+            AtlasError::Manifest { line, message } => write!(f, "line {line}: {message}"),
+            AtlasError::OutOfBounds { name, region } => write!(f, "sprite '{name}' at {region:?} is outside the sheet"),
+        }
+    }
+}
+
+impl Error for AtlasError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AtlasError::Io(err) => Some(err),
+            AtlasError::Png(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AtlasError {
+    fn from(err: io::Error) -> Self {
+        AtlasError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for AtlasError {
+    fn from(err: png::DecodingError) -> Self {
+        AtlasError::Png(err)
+    }
+}
+
+/// A sprite sheet and the named regions sliced out of it
+pub struct Atlas {
+    pub image: Rc<Image>,
+    regions: HashMap<String, Region>,
+}
+
+impl Atlas {
+    /// An atlas with no regions yet
+    pub fn new(image: Image) -> Self {
+        Self {
+            image: Rc::new(image),
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Slice the whole image into cells of `width` by `height`, named `prefix` followed by their index,
+    /// counting left to right then top to bottom
+    pub fn add_grid(&mut self, prefix: &str, width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let columns = self.image.width / width;
+        for row in 0..self.image.height / height {
+            for column in 0..columns {
+                let name = format!("{prefix}{}", row * columns + column);
+                self.regions.insert(name, Region::new(column * width, row * height, width, height));
+            }
+        }
+    }
+
+    /// Name a region of the sheet
+    pub fn add(&mut self, name: &str, region: Region) -> Result<(), AtlasError> {
+        if !self.image.contains(region) {
+            return Err(AtlasError::OutOfBounds {
+                name: name.to_string(),
+                region,
+            });
+        }
+        self.regions.insert(name.to_string(), region);
+        Ok(())
+    }
+
+    /// The region called `name`
+    pub fn get(&self, name: &str) -> Option<Region> {
+        self.regions.get(name).copied()
+    }
+
+    /// Load a PNG sheet sliced into a grid of `width` by `height` cells named by index
+    pub fn load_grid(path: &Path, width: usize, height: usize) -> Result<Self, AtlasError> {
+        let mut atlas = Self::new(Image::load_png(path)?);
+        atlas.add_grid("", width, height);
+        Ok(atlas)
+    }
+
+    /// Load a manifest and the sheet it names, relative to the manifest
+    pub fn load_manifest(path: &Path) -> Result<Self, AtlasError> {
+        let text = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let mut atlas: Option<Self> = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad_line = |message: String| AtlasError::Manifest { line: i + 1, message };
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| bad_line("expected `key: value`".to_string()))?;
+            let value = value.trim();
+            if key.trim() == "image" {
+                atlas = Some(Self::new(Image::load_png(&dir.join(value))?));
+                continue;
+            }
+
+            let atlas = atlas
+                .as_mut()
+                .ok_or_else(|| bad_line("the `image` has to come before any sprites".to_string()))?;
+            let (name, numbers) = value
+                .split_once('=')
+                .ok_or_else(|| bad_line(format!("expected `name = numbers`, found '{value}'")))?;
+            let name = name.trim();
+            let numbers: Vec<usize> = numbers
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| bad_line(format!("invalid numbers in '{value}'")))?;
+            match (key.trim(), numbers.as_slice()) {
+                ("sprite", &[x, y, width, height]) => atlas.add(name, Region::new(x, y, width, height))?,
+                ("grid", &[width, height]) => atlas.add_grid(name, width, height),
+                ("sprite" | "grid", _) => return Err(bad_line(format!("wrong number of values in '{value}'"))),
+                (other, _) => warn!("Ignoring unknown manifest key '{other}' on line {}", i + 1),
+            }
+        }
+        atlas.ok_or_else(|| AtlasError::Manifest {
+            line: text.lines().count(),
+            message: "no `image` given".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(ASSET_DIR).join(name)
+    }
+
+    #[test]
+    fn grid_slices_left_to_right_then_down() {
+        let mut atlas = Atlas::new(Image::filled(30, 20, [0; 4]));
+        atlas.add_grid("cell", 10, 10);
+        assert_eq!(atlas.get("cell0"), Some(Region::new(0, 0, 10, 10)));
+        assert_eq!(atlas.get("cell2"), Some(Region::new(20, 0, 10, 10)));
+        assert_eq!(atlas.get("cell3"), Some(Region::new(0, 10, 10, 10)));
+        assert_eq!(atlas.get("cell6"), None);
+    }
+
+    #[test]
+    fn regions_must_fit_the_sheet() {
+        let mut atlas = Atlas::new(Image::filled(16, 16, [0; 4]));
+        assert!(atlas.add("fits", Region::new(8, 8, 8, 8)).is_ok());
+        assert!(matches!(atlas.add("too wide", Region::new(9, 0, 8, 8)), Err(AtlasError::OutOfBounds { .. })));
+        assert!(!atlas.image.contains(Region::new(usize::MAX, 0, 8, 8)));
+        assert!(!atlas.image.contains(Region::new(0, 8, 8, usize::MAX)));
+    }
+
+    #[test]
+    fn bundled_blocks_load() {
+        let atlas = Atlas::load_manifest(&fixture("blocks.atlas")).unwrap();
+        for name in ["empty", "block", "grass"] {
+            let region = atlas.get(name).unwrap_or_else(|| panic!("no sprite '{name}'"));
+            assert_eq!((region.width, region.height), (10, 10));
+        }
+        let empty = atlas.get("empty").unwrap();
+        assert_eq!(atlas.image.pixel(empty.x, empty.y)[3], 0);
+
+        let grid = Atlas::load_grid(&fixture("blocks.png"), 10, 10).unwrap();
+        assert_eq!(grid.get("1"), atlas.get("block"));
+    }
+}
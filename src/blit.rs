@@ -0,0 +1,271 @@
+//! Copying sprite pixels into the frame, clipped to its edges.
+//!
+//! Kept free of the rest of the game so the benchmarks can include it on its own.
+
+use std::rc::Rc;
+
+/// A rectangle of an RGBA image to draw from
+#[derive(Copy, Clone)]
+pub struct View<'a> {
+    /// The whole image, row-major with 4 bytes per pixel
+    pub pixels: &'a [u8],
+    /// Width of the whole image in pixels
+    pub stride: usize,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl View<'_> {
+    /// True if every pixel in the view is fully opaque, so it can be copied without blending
+    pub fn is_opaque(&self) -> bool {
+        (self.y..self.y + self.height).all(|y| {
+            let start = (y * self.stride + self.x) * 4;
+            self.pixels[start..start + self.width * 4].chunks_exact(4).all(|pixel| pixel[3] == 255)
+        })
+    }
+}
+
+/// How a sprite's pixels are combined with the frame underneath
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawMode {
+    /// Overwrite the frame, alpha and all. Only right for fully opaque sprites, but a row at a time.
+    Copy,
+    /// Blend over the frame by the sprite's alpha
+    Alpha,
+    /// Add the sprite's colour, scaled by its alpha, to the frame
+    Add,
+    /// Darken the frame by the sprite's colour, scaled by its alpha
+    Multiply,
+    /// Multiply the sprite's colour and alpha by a tint, then blend it over the frame
+    Tint([u8; 4]),
+    /// Look up each pixel's red channel in a palette and blend that colour over the frame. Indices past the
+    /// end of the palette keep the sprite's own colour.
+    Palette(Rc<[[u8; 4]]>),
+}
+
+/// Draw `view` into a frame `frame_width` pixels wide with its top left corner at `at`.
+///
+/// Anything outside the frame is clipped on each axis separately, so a sprite hanging off one edge never
+/// wraps around to the other.
+pub fn blit(frame: &mut [u8], frame_width: usize, view: View, at: (i32, i32), mode: &DrawMode) {
+    let frame_height = frame.len() / 4 / frame_width;
+
+    // The part of the view that lands inside the frame
+    let left = at.0.max(0);
+    let top = at.1.max(0);
+    let right = (at.0 + view.width as i32).min(frame_width as i32);
+    let bottom = (at.1 + view.height as i32).min(frame_height as i32);
+    if left >= right || top >= bottom {
+        return;
+    }
+    let width = (right - left) as usize;
+    let src_x = view.x + (left - at.0) as usize;
+    let src_y = view.y + (top - at.1) as usize;
+
+    for row in 0..(bottom - top) as usize {
+        let src_start = ((src_y + row) * view.stride + src_x) * 4;
+        let dst_start = ((top as usize + row) * frame_width + left as usize) * 4;
+        let src = &view.pixels[src_start..src_start + width * 4];
+        let dst = &mut frame[dst_start..dst_start + width * 4];
+        match mode {
+            DrawMode::Copy => dst.copy_from_slice(src),
+            DrawMode::Tint(tint) => tint_row(dst, src, *tint),
+            _ => {
+                for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                    draw_pixel(dst, src, mode);
+                }
+            }
+        }
+    }
+}
+
+/// Combine one sprite pixel with one frame pixel
+fn draw_pixel(dst: &mut [u8], src: &[u8], mode: &DrawMode) {
+    match mode {
+        DrawMode::Copy => dst.copy_from_slice(src),
+        DrawMode::Alpha => blend(dst, src),
+        DrawMode::Add => {
+            for channel in 0..3 {
+                dst[channel] = dst[channel].saturating_add(scale(src[channel], src[3]));
+            }
+        }
+        DrawMode::Multiply => {
+            for channel in 0..3 {
+                // Fade the multiplier towards white as the pixel gets more transparent
+                let factor = 255 - scale(255 - src[channel], src[3]);
+                dst[channel] = scale(dst[channel], factor);
+            }
+        }
+        DrawMode::Tint(tint) => {
+            let tinted = [0, 1, 2, 3].map(|channel| scale(src[channel], tint[channel]));
+            blend(dst, &tinted);
+        }
+        DrawMode::Palette(palette) => match palette.get(src[0] as usize) {
+            Some(color) => blend(dst, &[color[0], color[1], color[2], scale(color[3], src[3])]),
+            None => blend(dst, src),
+        },
+    }
+}
+
+/// Tint a row of sprite pixels over a row of the frame. Pixels that stay opaque are written straight through,
+/// the same as `Copy`, so an opaque tint of an opaque sprite never blends.
+fn tint_row(dst: &mut [u8], src: &[u8], tint: [u8; 4]) {
+    for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        let tinted = [
+            scale(src[0], tint[0]),
+            scale(src[1], tint[1]),
+            scale(src[2], tint[2]),
+            scale(src[3], tint[3]),
+        ];
+        match tinted[3] {
+            255 => dst.copy_from_slice(&tinted),
+            0 => {}
+            _ => blend(dst, &tinted),
+        }
+    }
+}
+
+/// `value * factor / 255`
+fn scale(value: u8, factor: u8) -> u8 {
+    (value as u16 * factor as u16 / 255) as u8
+}
+
+/// Blend the `src` pixel over the `dst` pixel in the frame using the source alpha
+pub fn blend(dst: &mut [u8], src: &[u8]) {
+    match src[3] {
+        0 => {}
+        255 => dst.copy_from_slice(src),
+        alpha => {
+            let alpha = alpha as u16;
+            for channel in 0..3 {
+                dst[channel] = ((src[channel] as u16 * alpha + dst[channel] as u16 * (255 - alpha)) / 255) as u8;
+            }
+            dst[3] = 255;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 8;
+    const HEIGHT: usize = 6;
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    /// A 3x2 red sprite
+    fn red_view(pixels: &[u8]) -> View<'_> {
+        View {
+            pixels,
+            stride: 3,
+            x: 0,
+            y: 0,
+            width: 3,
+            height: 2,
+        }
+    }
+
+    /// Draw the red sprite at `at` and return which frame pixels turned red, as rows of `#` and `.`
+    fn draw_at(at: (i32, i32)) -> Vec<String> {
+        let pixels = RED.repeat(6);
+        let mut frame = BLACK.repeat(WIDTH * HEIGHT);
+        blit(&mut frame, WIDTH, red_view(&pixels), at, &DrawMode::Copy);
+        frame
+            .chunks_exact(WIDTH * 4)
+            .map(|row| row.chunks_exact(4).map(|pixel| if pixel == RED { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn draws_inside_the_frame() {
+        assert_eq!(draw_at((2, 1)), ["........", "..###...", "..###...", "........", "........", "........"]);
+    }
+
+    #[test]
+    fn clips_each_edge_without_wrapping() {
+        assert_eq!(draw_at((-2, 0))[..2], ["#.......", "#......."]);
+        assert_eq!(draw_at((6, 0))[..2], ["......##", "......##"]);
+        assert_eq!(draw_at((0, -1))[..2], ["###.....", "........"]);
+        assert_eq!(draw_at((0, 5))[4..], ["........", "###....."]);
+    }
+
+    #[test]
+    fn fully_outside_draws_nothing() {
+        let empty = vec![".".repeat(WIDTH); HEIGHT];
+        for at in [(-3, 0), (8, 0), (0, -2), (0, 6), (-100, -100)] {
+            assert_eq!(draw_at(at), empty, "sprite at {at:?}");
+        }
+    }
+
+    #[test]
+    fn translucent_pixels_blend() {
+        let pixels = [255, 0, 0, 0].repeat(3).into_iter().chain([255, 255, 255, 51].repeat(3)).collect::<Vec<_>>();
+        let view = red_view(&pixels);
+        assert!(!view.is_opaque());
+        let mut frame = BLACK.repeat(WIDTH * HEIGHT);
+        blit(&mut frame, WIDTH, view, (0, 0), &DrawMode::Alpha);
+        assert_eq!(frame[..4], BLACK);
+        assert_eq!(frame[WIDTH * 4..WIDTH * 4 + 4], [51, 51, 51, 255]);
+    }
+
+    #[test]
+    fn views_read_from_their_region() {
+        // Columns 0, 1, 2 of a 3x1 image, viewing only the last two
+        let pixels = [BLACK, RED, RED].concat();
+        let view = View { x: 1, width: 2, height: 1, ..red_view(&pixels) };
+        assert!(view.is_opaque());
+        let mut frame = BLACK.repeat(WIDTH * HEIGHT);
+        blit(&mut frame, WIDTH, view, (0, 0), &DrawMode::Copy);
+        assert_eq!(frame[..12], [RED, RED, BLACK].concat());
+    }
+
+    /// Draw a single `src` pixel over a single `dst` pixel
+    fn draw_one(dst: [u8; 4], src: [u8; 4], mode: DrawMode) -> [u8; 4] {
+        let view = View {
+            pixels: &src,
+            stride: 1,
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        let mut frame = dst;
+        blit(&mut frame, 1, view, (0, 0), &mode);
+        frame
+    }
+
+    #[test]
+    fn transparent_pixels_leave_the_frame_alone() {
+        let under = [10, 20, 30, 255];
+        for mode in [DrawMode::Alpha, DrawMode::Add, DrawMode::Multiply, DrawMode::Tint([255; 4])] {
+            assert_eq!(draw_one(under, [200, 200, 200, 0], mode.clone()), under, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn add_and_multiply_combine_channels() {
+        assert_eq!(draw_one([100, 200, 0, 255], [100, 100, 0, 255], DrawMode::Add), [200, 255, 0, 255]);
+        assert_eq!(draw_one([100, 200, 0, 255], [100, 51, 0, 127], DrawMode::Add), [149, 225, 0, 255]);
+        assert_eq!(draw_one([200, 200, 200, 255], [255, 0, 127, 255], DrawMode::Multiply), [200, 0, 99, 255]);
+    }
+
+    #[test]
+    fn tint_colours_a_grey_sprite() {
+        let grey = [128, 128, 128, 255];
+        assert_eq!(draw_one(BLACK, grey, DrawMode::Tint([255, 0, 255, 255])), [128, 0, 128, 255]);
+        assert_eq!(draw_one(BLACK, [255; 4], DrawMode::Tint([255, 255, 255, 51])), [51, 51, 51, 255]);
+    }
+
+    #[test]
+    fn palette_swaps_indexed_pixels() {
+        let palette: Rc<[[u8; 4]]> = Rc::new([[0, 0, 255, 255], [0, 255, 0, 255]]);
+        let mode = DrawMode::Palette(palette);
+        assert_eq!(draw_one(BLACK, [1, 0, 0, 255], mode.clone()), [0, 255, 0, 255]);
+        assert_eq!(draw_one(BLACK, [0, 0, 0, 255], mode.clone()), [0, 0, 255, 255]);
+        // Past the end of the palette
+        assert_eq!(draw_one(BLACK, [2, 9, 9, 255], mode), [2, 9, 9, 255]);
+    }
+}
@@ -0,0 +1,201 @@
+//! The playfield: a grid of locked cells with collision checks and line clearing.
+
+/// Number of columns in the well
+pub const BOARD_WIDTH: usize = 10;
+/// Total number of rows, including the hidden spawn buffer above the visible well
+pub const BOARD_HEIGHT: usize = 40;
+/// Number of rows the player can actually see
+pub const VISIBLE_HEIGHT: usize = 20;
+/// Rows `0..HIDDEN_ROWS` are the spawn buffer and are never drawn
+pub const HIDDEN_ROWS: usize = BOARD_HEIGHT - VISIBLE_HEIGHT;
+
+/// Tile id of an empty cell
+pub const EMPTY: u8 = 0;
+
+/// The locked cells of the well, stored row-major with row 0 at the top of the spawn buffer.
+///
+/// Every cell holds the id of the `Tile` used to draw it, `EMPTY` meaning nothing is there.
+#[derive(Clone)]
+pub struct Board {
+    cells: Vec<u8>,
+}
+
+impl Board {
+    /// Create an empty `Board`
+    pub fn new() -> Self {
+        Self {
+            cells: vec![EMPTY; BOARD_WIDTH * BOARD_HEIGHT],
+        }
+    }
+
+    /// Build a `Board` from a map string, one line per row, for tests. Levels are read by `Level::parse`.
+    ///
+    /// Rows are aligned to the bottom of the well, each character is a tile id digit.
+    /// Columns past `BOARD_WIDTH` and rows past the top of the board are ignored.
+    #[cfg(test)]
+    pub fn from_map_string(map: &str) -> Self {
+        let mut board = Self::new();
+        let rows: Vec<&str> = map
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        for (i, line) in rows.iter().rev().enumerate().take(BOARD_HEIGHT) {
+            let y = BOARD_HEIGHT - 1 - i;
+            for (x, c) in line.chars().enumerate().take(BOARD_WIDTH) {
+                let id = c.to_digit(10).unwrap_or_else(|| panic!("'{c}' is not a tile id digit"));
+                board.set(x, y, id as u8);
+            }
+        }
+        board
+    }
+
+    /// Tile id at `(x, y)`, or `None` if the position is outside the board
+    pub fn get(&self, x: i32, y: i32) -> Option<u8> {
+        if x < 0 || y < 0 || x >= BOARD_WIDTH as i32 || y >= BOARD_HEIGHT as i32 {
+            return None;
+        }
+        Some(self.cells[y as usize * BOARD_WIDTH + x as usize])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, id: u8) {
+        self.cells[y * BOARD_WIDTH + x] = id;
+    }
+
+    /// True if `(x, y)` is a wall, the floor, or a locked cell
+    pub fn is_occupied(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) != Some(EMPTY)
+    }
+
+    /// True if none of the given cells collide with the walls, floor or stack
+    pub fn fits(&self, cells: impl IntoIterator<Item = (i32, i32)>) -> bool {
+        cells.into_iter().all(|(x, y)| !self.is_occupied(x, y))
+    }
+
+    /// Write the given cells into the board with tile `id`
+    pub fn lock(&mut self, cells: impl IntoIterator<Item = (i32, i32)>, id: u8) {
+        for (x, y) in cells {
+            if self.get(x, y).is_some() {
+                self.set(x as usize, y as usize, id);
+            }
+        }
+    }
+
+    /// Remove every full row, shifting the rows above it down. Returns the number of rows removed.
+    pub fn clear_lines(&mut self) -> usize {
+        let mut kept: Vec<u8> = Vec::with_capacity(self.cells.len());
+        let mut cleared = 0;
+        for row in self.cells.chunks_exact(BOARD_WIDTH) {
+            if row.iter().all(|&id| id != EMPTY) {
+                cleared += 1;
+            } else {
+                kept.extend_from_slice(row);
+            }
+        }
+
+        // Refill the top of the board with the number of rows that were removed
+        self.cells = vec![EMPTY; cleared * BOARD_WIDTH];
+        self.cells.extend(kept);
+        cleared
+    }
+
+    /// Empty every cell in the given rows without moving the rows around them
+    pub fn clear_rows(&mut self, rows: std::ops::Range<usize>) {
+        let end = rows.end.min(BOARD_HEIGHT);
+        self.cells[rows.start * BOARD_WIDTH..end * BOARD_WIDTH].fill(EMPTY);
+    }
+
+    /// Number of rows holding at least one cell whose id matches `predicate`
+    pub fn count_rows(&self, predicate: impl Fn(u8) -> bool) -> usize {
+        self.cells
+            .chunks_exact(BOARD_WIDTH)
+            .filter(|row| row.iter().any(|&id| predicate(id)))
+            .count()
+    }
+
+    /// True if no cells are locked anywhere on the board
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|&id| id == EMPTY)
+    }
+
+    /// Iterate over the visible rows, top to bottom
+    pub fn visible_rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.cells.chunks_exact(BOARD_WIDTH).skip(HIDDEN_ROWS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTTOM: i32 = BOARD_HEIGHT as i32 - 1;
+
+    fn row(board: &Board, y: i32) -> Vec<u8> {
+        (0..BOARD_WIDTH as i32).map(|x| board.get(x, y).unwrap()).collect()
+    }
+
+    #[test]
+    fn cells_fit_inside_the_walls_and_above_the_stack() {
+        let board = Board::from_map_string("0000010000");
+        assert!(board.fits([(0, 0), (9, BOTTOM), (4, BOTTOM)]));
+        // Walls and floor
+        assert!(!board.fits([(-1, 10)]));
+        assert!(!board.fits([(BOARD_WIDTH as i32, 10)]));
+        assert!(!board.fits([(3, BOARD_HEIGHT as i32)]));
+        // The stack
+        assert!(!board.fits([(4, BOTTOM - 1), (5, BOTTOM)]));
+        assert!(board.fits(std::iter::empty()));
+    }
+
+    #[test]
+    fn locking_writes_the_tile_and_skips_cells_off_the_board() {
+        let mut board = Board::new();
+        board.lock([(0, BOTTOM), (1, BOTTOM), (1, BOTTOM - 1), (-1, BOTTOM), (2, BOARD_HEIGHT as i32)], 7);
+        assert_eq!(row(&board, BOTTOM), [7, 7, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(row(&board, BOTTOM - 1), [0, 7, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(board.is_occupied(1, BOTTOM - 1));
+        assert!(!board.is_occupied(2, BOTTOM - 1));
+    }
+
+    #[test]
+    fn clearing_full_rows_drops_the_rows_above() {
+        let mut board = Board::from_map_string(
+            "2000000000
+             1111111111
+             0300000000
+             1111111111
+             0040000000",
+        );
+        assert_eq!(board.clear_lines(), 2);
+        // What was between the full rows sits on what was below them, what was above on top of that
+        assert_eq!(row(&board, BOTTOM), [0, 0, 4, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(row(&board, BOTTOM - 1), [0, 3, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(row(&board, BOTTOM - 2), [2, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(board.count_rows(|id| id != EMPTY), 3);
+        assert_eq!(board.clear_lines(), 0);
+    }
+
+    #[test]
+    fn clearing_rows_leaves_the_rest_in_place() {
+        let mut board = Board::from_map_string(
+            "1000000000
+             0200000000
+             0030000000",
+        );
+        assert_eq!(board.count_rows(|id| id != EMPTY), 3);
+        assert_eq!(board.count_rows(|id| id == 2), 1);
+
+        board.clear_rows(BOARD_HEIGHT - 2..BOARD_HEIGHT - 1);
+        assert_eq!(row(&board, BOTTOM - 2), [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(row(&board, BOTTOM - 1).iter().all(|&id| id == EMPTY));
+        assert_eq!(row(&board, BOTTOM), [0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(!board.is_empty());
+
+        // Ranges past the bottom stop there
+        board.clear_rows(0..BOARD_HEIGHT + 5);
+        assert!(board.is_empty());
+        assert_eq!(board.count_rows(|id| id != EMPTY), 0);
+        assert!(Board::new().is_empty());
+    }
+}
@@ -0,0 +1,357 @@
+//! Bitmap fonts for drawing text into the frame.
+//!
+//! A font is a sheet of white glyphs on a transparent background, tinted when drawn. The sheet is either a
+//! monospace grid holding the characters of a string in order, or described by a BMFont text descriptor:
+//!
+//! ```text
+//! common lineHeight=7 base=5 scaleW=64 scaleH=42 pages=1
+//! page id=0 file="font.png"
+//! char id=65 x=4 y=12 width=4 height=6 xoffset=0 yoffset=0 xadvance=4 page=0
+//! ```
+//!
+//! Only single page descriptors are supported. Characters the font lacks are drawn as `?`.
+
+use crate::atlas::{AtlasError, Image, Region};
+use crate::blit::{blit, DrawMode, View};
+use crate::INTERNAL_WIDTH;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// BMFont descriptor of the bundled font, in the asset directory
+pub const FONT_FILE: &str = "font.fnt";
+
+/// The characters of the bundled monospace sheet in grid order: printable ASCII, an unused cell and a few
+/// symbols
+pub const SHEET_CHARS: &str = concat!(
+    " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~\u{7f}",
+    "©°±·×←↑→↓•…♥█",
+);
+
+/// Drawn in place of characters the font doesn't have
+const REPLACEMENT: char = '?';
+
+/// How a line of text sits relative to the x coordinate it is drawn at
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Colour, alignment and shadow for drawing text
+#[derive(Copy, Clone, Debug)]
+pub struct TextStyle {
+    pub color: [u8; 4],
+    pub align: Align,
+    /// Colour of a copy drawn one pixel down and to the right, underneath the text
+    pub shadow: Option<[u8; 4]>,
+}
+
+impl TextStyle {
+    /// Left aligned text in `color` without a shadow
+    pub const fn new(color: [u8; 4]) -> Self {
+        Self {
+            color,
+            align: Align::Left,
+            shadow: None,
+        }
+    }
+
+    pub const fn align(self, align: Align) -> Self {
+        Self { align, ..self }
+    }
+
+    pub const fn shadow(self, color: [u8; 4]) -> Self {
+        Self {
+            shadow: Some(color),
+            ..self
+        }
+    }
+}
+
+/// Where a glyph is on the sheet and how it sits on the line
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Glyph {
+    region: Region,
+    /// From the pen position to the top left corner of the glyph
+    offset: (i32, i32),
+    /// How far the pen moves after the glyph
+    advance: i32,
+}
+
+pub struct Font {
+    image: Rc<Image>,
+    glyphs: HashMap<char, Glyph>,
+    /// Distance between the tops of consecutive lines
+    pub line_height: i32,
+}
+
+impl Font {
+    /// A font with no glyphs yet, which draws nothing
+    pub fn new(image: Image, line_height: i32) -> Self {
+        Self {
+            image: Rc::new(image),
+            glyphs: HashMap::new(),
+            line_height,
+        }
+    }
+
+    /// Load a monospace sheet of `width` by `height` cells holding `chars` left to right then top to bottom
+    pub fn load_grid(path: &Path, width: usize, height: usize, chars: &str) -> Result<Self, AtlasError> {
+        let mut font = Self::new(Image::load_png(path)?, height as i32);
+        let columns = font.image.width / width.max(1);
+        if columns == 0 {
+            if let Some(c) = chars.chars().next() {
+                return Err(AtlasError::OutOfBounds {
+                    name: c.to_string(),
+                    region: Region::new(0, 0, width, height),
+                });
+            }
+        }
+        for (i, c) in chars.chars().enumerate() {
+            let region = Region::new(i % columns * width, i / columns * height, width, height);
+            if !font.image.contains(region) {
+                return Err(AtlasError::OutOfBounds {
+                    name: c.to_string(),
+                    region,
+                });
+            }
+            let glyph = Glyph {
+                region,
+                offset: (0, 0),
+                advance: width as i32,
+            };
+            font.glyphs.insert(c, glyph);
+        }
+        Ok(font)
+    }
+
+    /// Load a BMFont text descriptor and the sheet it names, relative to the descriptor
+    pub fn load_fnt(path: &Path) -> Result<Self, AtlasError> {
+        let text = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let mut line_height = None;
+        let mut font: Option<Self> = None;
+        for (i, line) in text.lines().enumerate() {
+            let bad_line = |message: String| AtlasError::Manifest { line: i + 1, message };
+            let (tag, fields) = parse_fnt_line(line);
+            let number = |key: &str| parse_field::<i32>(&fields, tag, key).map_err(bad_line);
+            // Positions and sizes on the sheet can't be negative
+            let size = |key: &str| parse_field::<usize>(&fields, tag, key).map_err(bad_line);
+            match tag {
+                "common" => {
+                    if number("pages")? != 1 {
+                        return Err(bad_line("only fonts with a single page are supported".to_string()));
+                    }
+                    line_height = Some(number("lineHeight")?);
+                }
+                "page" => {
+                    let file = fields.get("file").ok_or_else(|| bad_line("`page` is missing `file`".to_string()))?;
+                    let height = line_height.ok_or_else(|| bad_line("`common` has to come before `page`".to_string()))?;
+                    font = Some(Self::new(Image::load_png(&dir.join(file))?, height));
+                }
+                "char" => {
+                    let font = font
+                        .as_mut()
+                        .ok_or_else(|| bad_line("the `page` has to come before any chars".to_string()))?;
+                    let id = number("id")?;
+                    let c = char::from_u32(id as u32).ok_or_else(|| bad_line(format!("{id} is not a character")))?;
+                    let [x, y, width, height] = ["x", "y", "width", "height"].map(size);
+                    let region = Region::new(x?, y?, width?, height?);
+                    if !font.image.contains(region) {
+                        return Err(AtlasError::OutOfBounds {
+                            name: c.to_string(),
+                            region,
+                        });
+                    }
+                    let glyph = Glyph {
+                        region,
+                        offset: (number("xoffset")?, number("yoffset")?),
+                        advance: number("xadvance")?,
+                    };
+                    font.glyphs.insert(c, glyph);
+                }
+                // `info`, `chars`, `kernings` and `kerning` don't change how we draw
+                _ => {}
+            }
+        }
+        font.ok_or_else(|| AtlasError::Manifest {
+            line: text.lines().count(),
+            message: "no `page` given".to_string(),
+        })
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&REPLACEMENT))
+    }
+
+    /// Width in pixels of the widest line of `text`
+    pub fn measure(&self, text: &str) -> i32 {
+        text.lines()
+            .map(|line| line.chars().filter_map(|c| self.glyph(c)).map(|glyph| glyph.advance).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Draw `text` with the top of its first line at `y`, each line aligned to `x` by the style. Lines are
+    /// separated by `\n`.
+    pub fn draw(&self, frame: &mut [u8], text: &str, (x, y): (i32, i32), style: &TextStyle) {
+        if let Some(shadow) = style.shadow {
+            let shadow_style = TextStyle {
+                color: shadow,
+                shadow: None,
+                ..*style
+            };
+            self.draw(frame, text, (x + 1, y + 1), &shadow_style);
+        }
+
+        let mode = DrawMode::Tint(style.color);
+        for (row, line) in text.lines().enumerate() {
+            let mut pen_x = match style.align {
+                Align::Left => x,
+                Align::Center => x - self.measure(line) / 2,
+                Align::Right => x - self.measure(line),
+            };
+            let pen_y = y + row as i32 * self.line_height;
+            for glyph in line.chars().filter_map(|c| self.glyph(c)) {
+                let view = View {
+                    pixels: &self.image.pixels,
+                    stride: self.image.width,
+                    x: glyph.region.x,
+                    y: glyph.region.y,
+                    width: glyph.region.width,
+                    height: glyph.region.height,
+                };
+                let at = (pen_x + glyph.offset.0, pen_y + glyph.offset.1);
+                blit(frame, INTERNAL_WIDTH as usize, view, at, &mode);
+                pen_x += glyph.advance;
+            }
+        }
+    }
+}
+
+/// The value of `key` in the fields of a `tag` line, or what is wrong with it
+fn parse_field<T: FromStr>(fields: &HashMap<&str, &str>, tag: &str, key: &str) -> Result<T, String> {
+    let value = fields.get(key).ok_or_else(|| format!("`{tag}` is missing `{key}`"))?;
+    value
+        .parse()
+        .map_err(|_| format!("`{key}` should be a number, found '{value}'"))
+}
+
+/// Split a descriptor line into its tag and `key=value` fields, unquoting quoted values
+fn parse_fnt_line(line: &str) -> (&str, HashMap<&str, &str>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mut fields = HashMap::new();
+    loop {
+        rest = rest.trim_start();
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        fields.insert(key.trim(), value);
+        rest = after;
+    }
+    (tag, fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::ASSET_DIR;
+    use crate::INTERNAL_HEIGHT;
+    use std::env;
+
+    const WHITE: [u8; 4] = [255; 4];
+
+    fn bundled() -> Font {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(ASSET_DIR).join(FONT_FILE);
+        Font::load_fnt(&path).unwrap()
+    }
+
+    fn blank_frame() -> Vec<u8> {
+        [0, 0, 0, 255].repeat((INTERNAL_WIDTH * INTERNAL_HEIGHT) as usize)
+    }
+
+    /// Columns of the first text row that have any pixel of `color`
+    fn lit_columns(frame: &[u8], y: i32, color: [u8; 4]) -> Vec<usize> {
+        let row = &frame[(y * INTERNAL_WIDTH as i32 * 4) as usize..][..INTERNAL_WIDTH as usize * 4];
+        (0..INTERNAL_WIDTH as usize).filter(|x| row[x * 4..x * 4 + 4] == color).collect()
+    }
+
+    #[test]
+    fn descriptor_and_grid_agree() {
+        let font = bundled();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(ASSET_DIR).join("font.png");
+        let grid = Font::load_grid(&path, 4, 6, SHEET_CHARS).unwrap();
+        for c in SHEET_CHARS.chars().filter(|&c| c != '\u{7f}') {
+            assert_eq!(font.glyphs.get(&c), grid.glyphs.get(&c), "glyph {c:?}");
+        }
+        assert_eq!(font.line_height, 7);
+    }
+
+    #[test]
+    fn bad_sheets_are_errors() {
+        let sheet = Path::new(env!("CARGO_MANIFEST_DIR")).join(ASSET_DIR).join("font.png");
+        // Cells wider than the sheet
+        let err = Font::load_grid(&sheet, 1000, 6, "ab").err().unwrap();
+        assert!(matches!(err, AtlasError::OutOfBounds { name, .. } if name == "a"));
+
+        let descriptor = env::temp_dir().join(format!("bit_game_font_{}.fnt", std::process::id()));
+        let char_at = |x: i32| {
+            format!(
+                "common lineHeight=7 base=5 scaleW=64 scaleH=42 pages=1\npage id=0 file=\"{}\"\n\
+                 char id=65 x={x} y=12 width=4 height=6 xoffset=0 yoffset=0 xadvance=4 page=0\n",
+                sheet.display()
+            )
+        };
+        fs::write(&descriptor, char_at(4)).unwrap();
+        assert!(Font::load_fnt(&descriptor).is_ok());
+        fs::write(&descriptor, char_at(-1)).unwrap();
+        let err = Font::load_fnt(&descriptor).err();
+        fs::remove_file(&descriptor).unwrap();
+        assert!(matches!(err, Some(AtlasError::Manifest { line: 3, .. })));
+    }
+
+    #[test]
+    fn measures_the_widest_line() {
+        let font = bundled();
+        assert_eq!(font.measure("Score"), 20);
+        assert_eq!(font.measure("ab\n←→↑↓ok"), 24);
+        // Unknown characters are drawn as `?`
+        assert_eq!(font.measure("\u{1f600}"), 4);
+        assert_eq!(font.measure(""), 0);
+    }
+
+    #[test]
+    fn alignment_moves_the_line() {
+        let font = bundled();
+        let style = TextStyle::new(WHITE);
+        for (align, first, last) in [(Align::Left, 100, 111), (Align::Center, 94, 105), (Align::Right, 88, 99)] {
+            let mut frame = blank_frame();
+            font.draw(&mut frame, "███", (100, 10), &style.align(align));
+            let lit = lit_columns(&frame, 10, WHITE);
+            assert_eq!((lit[0], lit[lit.len() - 1]), (first, last), "{align:?}");
+        }
+    }
+
+    #[test]
+    fn text_is_tinted_with_a_shadow() {
+        let font = bundled();
+        let red = [255, 0, 0, 255];
+        let grey = [64, 64, 64, 255];
+        let mut frame = blank_frame();
+        font.draw(&mut frame, "█", (10, 10), &TextStyle::new(red).shadow(grey));
+        assert_eq!(lit_columns(&frame, 10, red), [10, 11, 12, 13]);
+        // The shadow peeks out below and to the right
+        assert_eq!(lit_columns(&frame, 16, grey), [11, 12, 13, 14]);
+        assert_eq!(lit_columns(&frame, 11, grey), [14]);
+    }
+}
@@ -0,0 +1,71 @@
+//! Garbage rows, generated as level map text so they load the same way as hand-authored levels.
+
+use crate::board::BOARD_WIDTH;
+use crate::rng::Rng;
+
+/// Tile id garbage is drawn with
+pub const GARBAGE_TILE_ID: u8 = 1;
+
+/// Generate `rows` rows of garbage with one hole each, as version 0 level text for `Level::parse`.
+///
+/// `messiness` is the percent chance the hole moves to a different column from one row to the next.
+pub fn garbage_map(rows: usize, messiness: u32, rng: &mut Rng) -> String {
+    let mut hole = rng.below(BOARD_WIDTH);
+    let mut map = String::with_capacity(rows * (BOARD_WIDTH + 1));
+    for row in 0..rows {
+        if row > 0 && (rng.below(100) as u32) < messiness {
+            hole = (hole + 1 + rng.below(BOARD_WIDTH - 1)) % BOARD_WIDTH;
+        }
+        for x in 0..BOARD_WIDTH {
+            if x == hole {
+                map.push('0');
+            } else {
+                map.push(char::from_digit(GARBAGE_TILE_ID as u32, 10).unwrap());
+            }
+        }
+        map.push('\n');
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Column of the hole in each row
+    fn holes(map: &str) -> Vec<usize> {
+        map.lines()
+            .map(|row| {
+                assert_eq!(row.len(), BOARD_WIDTH);
+                assert_eq!(row.matches('0').count(), 1, "row '{row}' should have one hole");
+                row.find('0').unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_row_has_one_hole() {
+        let map = garbage_map(12, 100, &mut Rng::new(7));
+        assert_eq!(holes(&map).len(), 12);
+        assert!(map.lines().all(|row| row.chars().all(|c| c == '0' || c == '1')));
+    }
+
+    #[test]
+    fn tidy_garbage_is_a_straight_well() {
+        let holes = holes(&garbage_map(10, 0, &mut Rng::new(7)));
+        assert!(holes.iter().all(|&hole| hole == holes[0]));
+    }
+
+    #[test]
+    fn messy_garbage_moves_the_hole_every_row() {
+        let holes = holes(&garbage_map(10, 100, &mut Rng::new(7)));
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn same_seed_same_garbage() {
+        let map = garbage_map(10, 50, &mut Rng::new(42));
+        assert_eq!(garbage_map(10, 50, &mut Rng::new(42)), map);
+        assert_ne!(garbage_map(10, 50, &mut Rng::new(43)), map);
+    }
+}
@@ -0,0 +1,27 @@
+//! Gravity and lock delay timing. Everything is counted in simulation frames so a game
+//! plays out the same way every time.
+
+/// Gravity is tracked in fractions of a cell so slow levels stay exact without floats
+pub const SUBCELLS_PER_CELL: u32 = 1 << 16;
+
+/// 20G, the piece drops to the floor the frame it spawns
+pub const MAX_GRAVITY: u32 = 20 * SUBCELLS_PER_CELL;
+
+/// Frames a grounded piece waits before locking, half a second at 60 Hz
+pub const LOCK_DELAY_FRAMES: u32 = 30;
+
+/// Number of times moving or rotating a grounded piece can restart its lock delay
+pub const MAX_LOCK_RESETS: u32 = 15;
+
+/// Guideline gravity for levels 1 to 18 in subcells per frame, from `(0.8 - (level - 1) * 0.007) ^ (level - 1)`
+/// seconds per row
+const GRAVITY_TABLE: [u32; 18] = [
+    1092, 1377, 1768, 2311, 3075, 4169, 5759, 8107, 11634, 17026, 25416, 38709, 60169, 95483,
+    154742, 256187, 433425, 749597,
+];
+
+/// Subcells the active piece falls every frame at `level`, level 19 and above is 20G
+pub fn gravity_for_level(level: u32) -> u32 {
+    let index = level.max(1) as usize - 1;
+    GRAVITY_TABLE.get(index).copied().unwrap_or(MAX_GRAVITY)
+}
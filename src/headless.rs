@@ -0,0 +1,188 @@
+//! Rendering without a window: the same draw code as the game, into a framebuffer in memory.
+//!
+//! Needs neither a display nor a GPU, so the golden image tests and `--screenshots` can run anywhere.
+//! Golden images live in `tests/fixtures/golden/`. Run the tests with `UPDATE_GOLDEN=1` to rewrite them after a
+//! deliberate change to how things look; a mismatch saves what was drawn next to the golden image as
+//! `<name>.actual.png`.
+//!
+//! The scenes only read levels from `tests/fixtures/levels/` and never a Sprint personal best, so editing the
+//! bundled levels or playing the game doesn't change them.
+
+use crate::atlas::Image;
+use crate::input::Action;
+use crate::level;
+use crate::modes::sprint::Sprint;
+use crate::modes::{GameMode, ModeKind};
+use crate::piece::Turn;
+use crate::settings::Settings;
+use crate::{draw_mode_menu, draw_puzzle_menu, load_font, load_tiles, Game, INTERNAL_HEIGHT, INTERNAL_WIDTH};
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Seed for the scenes, so every run deals the same pieces
+const SCENE_SEED: u64 = 1;
+/// Levels listed in the puzzle menu scene
+const SCENE_LEVEL_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/levels");
+/// Puzzle shown in the puzzle scene
+const SCENE_PUZZLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/levels/level_01.data");
+/// Ticks of hard dropping in the middle of the well, enough to lose the game. Every other tick only spawns the
+/// next piece.
+const TOP_OUT_TICKS: usize = 200;
+
+/// An RGBA frame the size of the game's internal resolution
+pub struct Framebuffer {
+    pub pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    /// A black frame
+    pub fn new() -> Self {
+        Self {
+            pixels: [0, 0, 0, 255].repeat((INTERNAL_WIDTH * INTERNAL_HEIGHT) as usize),
+        }
+    }
+
+    /// A frame filled in by `draw`, which gets the same buffer the window would
+    pub fn render(draw: impl FnOnce(&mut [u8])) -> Self {
+        let mut framebuffer = Self::new();
+        draw(&mut framebuffer.pixels);
+        framebuffer
+    }
+
+    /// Write the frame to a PNG file
+    pub fn save_png(&self, path: &Path) -> Result<(), EncodingError> {
+        let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), INTERNAL_WIDTH, INTERNAL_HEIGHT);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)
+    }
+
+    /// Number of pixels that differ from `image`, counting every pixel if the sizes differ
+    pub fn diff(&self, image: &Image) -> usize {
+        if image.width != INTERNAL_WIDTH as usize || image.height != INTERNAL_HEIGHT as usize {
+            return (INTERNAL_WIDTH * INTERNAL_HEIGHT) as usize;
+        }
+        self.pixels
+            .chunks_exact(4)
+            .zip(image.pixels.chunks_exact(4))
+            .filter(|(ours, theirs)| ours != theirs)
+            .count()
+    }
+}
+
+/// A game of `kind` with fixed settings after `actions`, one per tick. Sprint keeps no personal best.
+fn play(kind: ModeKind, settings: Settings, actions: &[Action]) -> Game {
+    let settings = Settings {
+        seed: Some(SCENE_SEED),
+        ..settings
+    };
+    let mode: Box<dyn GameMode> = match kind {
+        ModeKind::Sprint => Box::new(Sprint::with_save_dir(settings.sprint_lines, None)),
+        kind => kind.build(&settings),
+    };
+    let mut game = Game::with_mode(mode, load_tiles(), settings);
+    for &action in actions {
+        game.update(&[action]);
+    }
+    game
+}
+
+/// The screens worth a snapshot, by name: the menus, a game under way and a puzzle
+pub fn scenes() -> Vec<(&'static str, Framebuffer)> {
+    let font = load_font();
+    let puzzles = level::list_puzzles_in(Path::new(SCENE_LEVEL_DIR));
+
+    let marathon = play(
+        ModeKind::Marathon,
+        Settings::default(),
+        &[
+            Action::Shift(-4),
+            Action::HardDrop,
+            Action::Rotate(Turn::Clockwise),
+            Action::Shift(4),
+            Action::HardDrop,
+            Action::Hold,
+            Action::Shift(-1),
+            Action::HardDrop,
+            Action::Rotate(Turn::CounterClockwise),
+        ],
+    );
+    let puzzle_settings = Settings {
+        puzzle: Some(SCENE_PUZZLE.to_string()),
+        ..Settings::default()
+    };
+    let puzzle = play(ModeKind::Puzzle, puzzle_settings, &[]);
+    let sprint = play(ModeKind::Sprint, Settings::default(), &[Action::HardDrop; TOP_OUT_TICKS]);
+
+    vec![
+        ("mode_menu", Framebuffer::render(|frame| draw_mode_menu(frame, &font, 0))),
+        ("puzzle_menu", Framebuffer::render(|frame| draw_puzzle_menu(frame, &font, &puzzles, 1))),
+        ("marathon", Framebuffer::render(|frame| marathon.draw(frame, &font, 0.0))),
+        ("puzzle", Framebuffer::render(|frame| puzzle.draw(frame, &font, 0.0))),
+        ("game_over", Framebuffer::render(|frame| sprint.draw(frame, &font, 0.0))),
+    ]
+}
+
+/// Write every scene to `dir` as `<name>.png`, returning the files written
+pub fn write_screenshots(dir: &Path) -> Result<Vec<PathBuf>, EncodingError> {
+    fs::create_dir_all(dir)?;
+    scenes()
+        .into_iter()
+        .map(|(name, framebuffer)| {
+            let path = dir.join(format!("{name}.png"));
+            framebuffer.save_png(&path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::Status;
+    use std::env;
+
+    fn golden_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden")
+    }
+
+    #[test]
+    fn scenes_match_golden_images() {
+        let update = env::var_os("UPDATE_GOLDEN").is_some();
+        let mut mismatches = Vec::new();
+        for (name, framebuffer) in scenes() {
+            let path = golden_dir().join(format!("{name}.png"));
+            if update {
+                framebuffer.save_png(&path).unwrap();
+                continue;
+            }
+            let golden = Image::load_png(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            let differing = framebuffer.diff(&golden);
+            if differing > 0 {
+                let actual = golden_dir().join(format!("{name}.actual.png"));
+                framebuffer.save_png(&actual).unwrap();
+                mismatches.push(format!("{name}: {differing} pixels differ, see {}", actual.display()));
+            }
+        }
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+
+    #[test]
+    fn sprint_scene_has_topped_out() {
+        let game = play(ModeKind::Sprint, Settings::default(), &[Action::HardDrop; TOP_OUT_TICKS]);
+        assert_eq!(game.status, Status::ToppedOut);
+    }
+
+    #[test]
+    fn snapshots_round_trip_through_png() {
+        let framebuffer = Framebuffer::render(|frame| frame[..4].copy_from_slice(&[1, 2, 3, 255]));
+        let path = env::temp_dir().join(format!("bit_game_snapshot_{}.png", std::process::id()));
+        framebuffer.save_png(&path).unwrap();
+        let image = Image::load_png(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(framebuffer.diff(&image), 0);
+        assert_eq!(image.pixel(0, 0), [1, 2, 3, 255]);
+    }
+}
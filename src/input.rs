@@ -0,0 +1,272 @@
+//! Turns held buttons into game actions once per simulation tick, with delayed auto shift (DAS),
+//! auto repeat rate (ARR) and soft drop. All timings are in ticks, OS key repeat is never used.
+
+use crate::board::BOARD_WIDTH;
+use crate::piece::Turn;
+
+/// The game's buttons, whatever keys they are bound to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    SonicDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+}
+
+/// A set of buttons
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Buttons(u16);
+
+impl Buttons {
+    pub fn with(self, button: Button) -> Self {
+        Self(self.0 | 1 << button as u16)
+    }
+
+    pub fn contains(self, button: Button) -> bool {
+        self.0 & 1 << button as u16 != 0
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Move the piece this many cells sideways, negative is left. Stops at the first obstruction.
+    Shift(i32),
+    /// Soft drop is held this tick
+    SoftDrop,
+    HardDrop,
+    SonicDrop,
+    Rotate(Turn),
+    Hold,
+}
+
+pub struct InputHandler {
+    /// Ticks a direction must be held before it starts repeating
+    das: u32,
+    /// Ticks between repeats once DAS is charged, 0 moves straight to the wall
+    arr: u32,
+    previous: Buttons,
+    /// Presses since the last tick, so a tap shorter than a tick isn't lost
+    latched: Buttons,
+    /// Direction currently being shifted, the most recently pressed one wins
+    direction: i32,
+    das_timer: u32,
+    arr_timer: u32,
+}
+
+impl InputHandler {
+    pub fn new(das: u32, arr: u32) -> Self {
+        Self {
+            das,
+            arr,
+            previous: Buttons::default(),
+            latched: Buttons::default(),
+            direction: 0,
+            das_timer: 0,
+            arr_timer: 0,
+        }
+    }
+
+    /// Record that `button` went down, even if it is released again before the next tick
+    pub fn press(&mut self, button: Button) {
+        self.latched = self.latched.with(button);
+    }
+
+    /// Advance one tick with the buttons currently held. Returns the actions to apply this tick, in order.
+    pub fn tick(&mut self, held: Buttons) -> Vec<Action> {
+        let held = held.union(self.latched);
+        let pressed = Buttons(held.0 & !self.previous.0);
+        self.latched = Buttons::default();
+        self.previous = held;
+
+        let mut actions = Vec::new();
+        if pressed.contains(Button::Hold) {
+            actions.push(Action::Hold);
+        }
+        if pressed.contains(Button::RotateClockwise) {
+            actions.push(Action::Rotate(Turn::Clockwise));
+        }
+        if pressed.contains(Button::RotateCounterClockwise) {
+            actions.push(Action::Rotate(Turn::CounterClockwise));
+        }
+        if pressed.contains(Button::Rotate180) {
+            actions.push(Action::Rotate(Turn::Half));
+        }
+        if let Some(shift) = self.shift(held, pressed) {
+            actions.push(Action::Shift(shift));
+        }
+        if held.contains(Button::SoftDrop) {
+            actions.push(Action::SoftDrop);
+        }
+        if pressed.contains(Button::SonicDrop) {
+            actions.push(Action::SonicDrop);
+        }
+        // Hard drop goes last so the piece is moved and rotated before it locks
+        if pressed.contains(Button::HardDrop) {
+            actions.push(Action::HardDrop);
+        }
+        actions
+    }
+
+    /// Work out the sideways movement for this tick
+    fn shift(&mut self, held: Buttons, pressed: Buttons) -> Option<i32> {
+        let left = held.contains(Button::Left);
+        let right = held.contains(Button::Right);
+
+        // A fresh press takes over the direction, releasing it falls back to the other one if that is still held
+        let direction = if pressed.contains(Button::Right) {
+            1
+        } else if pressed.contains(Button::Left) {
+            -1
+        } else if (self.direction == 1 && right) || (self.direction == -1 && left) {
+            self.direction
+        } else if right {
+            1
+        } else if left {
+            -1
+        } else {
+            0
+        };
+
+        if direction == 0 {
+            self.direction = 0;
+            return None;
+        }
+
+        // Changing direction is a new tap: move once and start charging DAS again
+        if direction != self.direction || pressed.contains(Button::Left) || pressed.contains(Button::Right) {
+            self.direction = direction;
+            self.das_timer = 0;
+            self.arr_timer = 0;
+            return Some(direction);
+        }
+
+        if self.das_timer < self.das {
+            self.das_timer += 1;
+            if self.das_timer < self.das {
+                return None;
+            }
+        } else {
+            self.arr_timer += 1;
+            if self.arr > 0 && self.arr_timer < self.arr {
+                return None;
+            }
+        }
+
+        self.arr_timer = 0;
+        if self.arr == 0 {
+            Some(direction * BOARD_WIDTH as i32)
+        } else {
+            Some(direction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(buttons: &[Button]) -> Buttons {
+        buttons.iter().fold(Buttons::default(), |set, &button| set.with(button))
+    }
+
+    /// Feed a timeline of `(ticks, buttons held)` and collect the shift for every tick
+    fn shifts(handler: &mut InputHandler, timeline: &[(u32, &[Button])]) -> Vec<i32> {
+        let mut shifts = Vec::new();
+        for &(ticks, buttons) in timeline {
+            for _ in 0..ticks {
+                let shift = handler
+                    .tick(held(buttons))
+                    .into_iter()
+                    .find_map(|action| match action {
+                        Action::Shift(cells) => Some(cells),
+                        _ => None,
+                    })
+                    .unwrap_or(0);
+                shifts.push(shift);
+            }
+        }
+        shifts
+    }
+
+    #[test]
+    fn tap_moves_once() {
+        let mut handler = InputHandler::new(10, 2);
+        let shifts = shifts(&mut handler, &[(1, &[Button::Right]), (5, &[])]);
+        assert_eq!(shifts, [1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn held_direction_repeats_after_das_at_arr() {
+        let mut handler = InputHandler::new(10, 2);
+        let shifts = shifts(&mut handler, &[(16, &[Button::Left])]);
+
+        let moved_on: Vec<usize> = shifts
+            .iter()
+            .enumerate()
+            .filter(|(_, &shift)| shift != 0)
+            .map(|(tick, _)| tick)
+            .collect();
+        assert_eq!(moved_on, [0, 10, 12, 14]);
+        assert!(shifts.iter().all(|&shift| shift <= 0));
+    }
+
+    #[test]
+    fn zero_arr_moves_to_the_wall() {
+        let mut handler = InputHandler::new(6, 0);
+        let shifts = shifts(&mut handler, &[(8, &[Button::Right])]);
+        assert_eq!(shifts, [1, 0, 0, 0, 0, 0, 10, 10]);
+    }
+
+    #[test]
+    fn last_pressed_direction_wins() {
+        let mut handler = InputHandler::new(10, 2);
+        let shifts = shifts(
+            &mut handler,
+            &[
+                (3, &[Button::Left]),
+                (2, &[Button::Left, Button::Right]),
+                (2, &[Button::Left]),
+            ],
+        );
+        // Pressing right takes over, releasing it goes back to a fresh left tap
+        assert_eq!(shifts, [-1, 0, 0, 1, 0, -1, 0]);
+    }
+
+    #[test]
+    fn das_stays_charged_without_a_piece() {
+        // The handler knows nothing about the piece, so a direction held through the entry delay
+        // comes out charged and shifts the new piece straight away
+        let mut handler = InputHandler::new(4, 0);
+        let shifts = shifts(&mut handler, &[(20, &[Button::Right])]);
+        assert!(shifts[4..].iter().all(|&shift| shift == BOARD_WIDTH as i32));
+    }
+
+    #[test]
+    fn tap_between_ticks_is_not_lost() {
+        let mut handler = InputHandler::new(10, 2);
+        handler.press(Button::RotateClockwise);
+        handler.press(Button::Left);
+
+        let actions = handler.tick(Buttons::default());
+        assert_eq!(actions, [Action::Rotate(Turn::Clockwise), Action::Shift(-1)]);
+        assert!(handler.tick(Buttons::default()).is_empty());
+    }
+
+    #[test]
+    fn soft_drop_is_reported_every_tick_it_is_held() {
+        let mut handler = InputHandler::new(10, 2);
+        for _ in 0..5 {
+            assert_eq!(handler.tick(held(&[Button::SoftDrop])), [Action::SoftDrop]);
+        }
+        assert!(handler.tick(Buttons::default()).is_empty());
+    }
+}
@@ -0,0 +1,611 @@
+//! Level files: a header of `key: value` lines followed by the rows of the board.
+//!
+//! ```text
+//! version: 1
+//! name: First Tetris
+//! author: Bit World
+//! width: 10
+//! height: 2
+//! legend: . = empty
+//! legend: # = dirt
+//! legend: I = piece I
+//! legend: o = 12 #ff8000
+//! pieces: I
+//! hold: 0
+//! goal: lines 1
+//! ---
+//! ##.#####o#
+//! ###IIII###
+//! ```
+//!
+//! Files without a `version` line are read as version 0: optional header lines, then rows of tile id digits.
+//! Rows are aligned to the bottom of the well.
+
+use crate::board::{Board, BOARD_HEIGHT, BOARD_WIDTH, EMPTY};
+use crate::garbage::GARBAGE_TILE_ID;
+use crate::piece::{PieceKind, TILE_COUNT};
+use crate::tiled::{self, TiledError};
+use log::{error, warn};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory the level files live in
+pub const LEVEL_DIR: &str = "levels";
+
+/// Newest version of the level format this loader understands
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Line separating the header from the rows in versioned files
+const HEADER_END: &str = "---";
+
+/// Where in a level file a problem is, 1-based
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    /// The file could not be read
+    Io(io::Error),
+    /// A header line that can't be understood
+    BadHeader { at: Position, message: String },
+    /// A width or height that is missing, invalid, or doesn't match the rows
+    BadDimensions { at: Position, message: String },
+    /// A tile or legend symbol that doesn't name any tile
+    UnknownTile { at: Position, tile: String },
+    /// A row with a different number of cells than the level is wide
+    RaggedRow { at: Position, expected: usize, found: usize },
+    /// More rows or columns than the well has
+    TooLarge { at: Position, what: &'static str, found: usize, limit: usize },
+    /// A map from another editor that could not be imported
+    Import(TiledError),
+}
+
+impl LevelError {
+    /// Where the problem is, `None` if the file couldn't be read at all
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            LevelError::Io(_) | LevelError::Import(_) => None,
+            LevelError::BadHeader { at, .. }
+            | LevelError::BadDimensions { at, .. }
+            | LevelError::UnknownTile { at, .. }
+            | LevelError::RaggedRow { at, .. }
+            | LevelError::TooLarge { at, .. } => Some(*at),
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(at) = self.position() {
+            write!(f, "line {}, column {}: ", at.line, at.column)?;
+        }
+        match self {
+            LevelError::Io(err) => write!(f, "could not read the file: {err}"),
+            LevelError::Import(err) => err.fmt(f),
+            LevelError::BadHeader { message, .. } | LevelError::BadDimensions { message, .. } => f.write_str(message),
+            LevelError::UnknownTile { tile, .. } => write!(f, "unknown tile '{tile}'"),
+            LevelError::RaggedRow { expected, found, .. } => write!(f, "row has {found} cells, expected {expected}"),
+            LevelError::TooLarge { what, found, limit, .. } => write!(f, "{found} {what}, the well only has {limit}"),
+        }
+    }
+}
+
+impl Error for LevelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LevelError::Io(err) => Some(err),
+            LevelError::Import(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Log every problem found loading the level at `path`
+pub fn log_errors(path: &Path, errors: &[LevelError]) {
+    for err in errors {
+        error!("{}: {err}", path.display());
+    }
+}
+
+/// Column of the first non-whitespace character of `line`
+fn indent(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count() + 1
+}
+
+/// Tile id for a legend entry: a number, or the name of a level tile or piece
+pub fn parse_tile(name: &str) -> Option<u8> {
+    let id = match name.to_ascii_lowercase().as_str() {
+        "empty" => EMPTY,
+        "dirt" | "garbage" => GARBAGE_TILE_ID,
+        "grass" => 2,
+        name => match name.strip_prefix("piece") {
+            Some(piece) => {
+                let mut chars = piece.trim().chars();
+                let kind = chars.next().and_then(PieceKind::from_char)?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                kind.tile_id()
+            }
+            None => name.parse().ok()?,
+        },
+    };
+    Some(id)
+}
+
+/// Colour written as `#rrggbb`
+fn parse_color(text: &str) -> Option<[u8; 4]> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?, 255])
+}
+
+/// Split a legend entry such as `# = dirt` or `o = 12 #ff8000` into its symbol, tile id and optional colour.
+/// `at` is the position of the entry's first character.
+fn parse_legend_entry(entry: &str, at: Position) -> Result<(char, u8, Option<[u8; 4]>), LevelError> {
+    let bad_header = |message: String| LevelError::BadHeader { at, message };
+    let (symbol, tile) = entry
+        .split_once('=')
+        .ok_or_else(|| bad_header(format!("invalid legend entry '{entry}', expected `symbol = tile`")))?;
+    let mut symbol_chars = symbol.trim().chars();
+    let (Some(symbol), None) = (symbol_chars.next(), symbol_chars.next()) else {
+        return Err(bad_header("legend symbols must be a single character".to_string()));
+    };
+
+    let tile_column = at.column + entry.len() - tile.trim_start().len();
+    let tile = tile.trim();
+
+    // A tile can be followed by a colour, defining a new tile for that id
+    let (name, color) = match tile.rsplit_once(char::is_whitespace) {
+        Some((name, color)) if color.starts_with('#') => {
+            let color_at = Position::new(at.line, tile_column + tile.len() - color.len());
+            let color = parse_color(color).ok_or_else(|| LevelError::BadHeader {
+                at: color_at,
+                message: format!("invalid colour '{color}', expected #rrggbb"),
+            })?;
+            (name.trim(), Some((color, color_at)))
+        }
+        _ => (tile, None),
+    };
+    let id = parse_tile(name).ok_or_else(|| LevelError::UnknownTile {
+        at: Position::new(at.line, tile_column),
+        tile: name.to_string(),
+    })?;
+    // Recolouring the built-in tiles would change every piece or garbage block of that kind
+    if let Some((_, color_at)) = color.filter(|_| id < TILE_COUNT) {
+        return Err(LevelError::BadHeader {
+            at: color_at,
+            message: format!("only new tiles from {TILE_COUNT} up can have a colour, not tile {id}"),
+        });
+    }
+    Ok((symbol, id, color.map(|(color, _)| color)))
+}
+
+/// What a puzzle asks the player to do
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// Clear this many lines
+    Lines(u32),
+    /// Leave the board completely empty
+    PerfectClear,
+    /// Perform this many T-spins
+    TSpins(u32),
+}
+
+impl Goal {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut words = value.split_whitespace();
+        let goal = match words.next()?.to_ascii_lowercase().as_str() {
+            "lines" => Goal::Lines(words.next()?.parse().ok()?),
+            "perfect-clear" | "pc" => Goal::PerfectClear,
+            "tspins" | "t-spins" => Goal::TSpins(words.next()?.parse().ok()?),
+            _ => return None,
+        };
+        Some(goal)
+    }
+
+    /// Short description for the HUD
+    pub fn describe(self) -> String {
+        match self {
+            Goal::Lines(lines) => format!("Clear {lines} lines"),
+            Goal::PerfectClear => "Perfect clear".to_string(),
+            Goal::TSpins(count) => format!("{count} T-spins"),
+        }
+    }
+}
+
+pub struct Level {
+    pub version: u32,
+    pub name: String,
+    pub author: String,
+    pub board: Board,
+    /// Tiles the legend gives a colour, as tile id and colour
+    pub tiles: Vec<(u8, [u8; 4])>,
+    /// The pieces dealt, in order. Empty for levels that use the normal randomizer.
+    pub pieces: Vec<PieceKind>,
+    /// Number of times hold may be used, `None` for no limit
+    pub holds: Option<u32>,
+    pub goal: Option<Goal>,
+}
+
+impl Level {
+    /// A level with an empty board and no header
+    pub fn empty() -> Self {
+        Self {
+            version: 0,
+            name: String::new(),
+            author: String::new(),
+            board: Board::new(),
+            tiles: Vec::new(),
+            pieces: Vec::new(),
+            holds: None,
+            goal: None,
+        }
+    }
+
+    /// Parse a level from the text of a level file, reporting every problem found rather than just the first
+    pub fn parse(text: &str) -> Result<Self, Vec<LevelError>> {
+        let mut level = Self::empty();
+        let mut errors = Vec::new();
+        let mut width = BOARD_WIDTH;
+        let mut height = None;
+        // Version 0 files have no legend, every digit is its own tile id
+        let mut legend: HashMap<char, u8> = ('0'..='9').zip(0..).collect();
+        let mut has_legend = false;
+        // Line the rows start after, for problems with the rows as a whole
+        let mut header_end = 1;
+
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line)).peekable();
+
+        // Header lines come first, versioned files end them with `---`, older ones with the first row
+        while let Some(&(number, line)) = lines.peek() {
+            let trimmed = line.trim();
+            if level.version > 0 && trimmed == HEADER_END {
+                header_end = number;
+                lines.next();
+                break;
+            }
+            if trimmed.is_empty() {
+                lines.next();
+                continue;
+            }
+            let Some((key, value)) = trimmed.split_once(':') else {
+                if level.version > 0 {
+                    let at = Position::new(number, indent(line));
+                    errors.push(LevelError::BadHeader { at, message: format!("expected `{HEADER_END}` before the rows") });
+                }
+                header_end = number;
+                break;
+            };
+            let at = Position::new(number, indent(line) + key.len() + 1 + (value.len() - value.trim_start().len()));
+            let value = value.trim();
+            let bad_header = |message: String| LevelError::BadHeader { at, message };
+            match key.trim().to_ascii_lowercase().as_str() {
+                "version" => match value.parse() {
+                    _ if number != 1 => errors.push(bad_header("`version` must be the first line".to_string())),
+                    Ok(version) if (1..=FORMAT_VERSION).contains(&version) => level.version = version,
+                    _ => errors.push(bad_header(format!("unsupported version '{value}', expected 1 to {FORMAT_VERSION}"))),
+                },
+                "name" => level.name = value.to_string(),
+                "author" => level.author = value.to_string(),
+                "width" => match value.parse() {
+                    Ok(columns) if columns > BOARD_WIDTH => {
+                        width = columns;
+                        errors.push(LevelError::TooLarge { at, what: "columns", found: columns, limit: BOARD_WIDTH });
+                    }
+                    Ok(columns) if columns == BOARD_WIDTH => width = columns,
+                    _ => errors.push(LevelError::BadDimensions {
+                        at,
+                        message: format!("width must be {BOARD_WIDTH}, not '{value}'"),
+                    }),
+                },
+                "height" => match value.parse() {
+                    Ok(rows) if rows > BOARD_HEIGHT => {
+                        height = Some(rows);
+                        errors.push(LevelError::TooLarge { at, what: "rows", found: rows, limit: BOARD_HEIGHT });
+                    }
+                    Ok(rows) if rows > 0 => height = Some(rows),
+                    _ => errors.push(LevelError::BadDimensions {
+                        at,
+                        message: format!("height must be 1 to {BOARD_HEIGHT}, not '{value}'"),
+                    }),
+                },
+                "legend" => match parse_legend_entry(value, at) {
+                    Ok((symbol, id, color)) => {
+                        if !has_legend {
+                            legend.clear();
+                            has_legend = true;
+                        }
+                        legend.insert(symbol, id);
+                        if let Some(color) = color {
+                            level.tiles.push((id, color));
+                        } else if id >= TILE_COUNT && level.tiles.iter().all(|&(defined, _)| defined != id) {
+                            let tile = value.split_once('=').unwrap().1;
+                            let at = Position::new(at.line, at.column + value.len() - tile.trim_start().len());
+                            errors.push(LevelError::UnknownTile { at, tile: id.to_string() });
+                        }
+                    }
+                    Err(err) => errors.push(err),
+                },
+                "pieces" => {
+                    match value.chars().filter(|c| !c.is_whitespace()).map(PieceKind::from_char).collect() {
+                        Some(pieces) => level.pieces = pieces,
+                        None => errors.push(bad_header(format!("invalid piece list '{value}'"))),
+                    }
+                }
+                "hold" => match value.parse() {
+                    Ok(holds) => level.holds = Some(holds),
+                    Err(_) => errors.push(bad_header(format!("invalid hold count '{value}'"))),
+                },
+                "goal" => match Goal::parse(value) {
+                    Some(goal) => level.goal = Some(goal),
+                    None => errors.push(bad_header(format!("invalid goal '{value}'"))),
+                },
+                other => errors.push(LevelError::BadHeader {
+                    at: Position::new(number, indent(line)),
+                    message: format!("unknown header '{other}'"),
+                }),
+            }
+            lines.next();
+        }
+
+        if level.version > 0 && height.is_none() {
+            errors.push(LevelError::BadDimensions {
+                at: Position::new(header_end, 1),
+                message: "versioned levels need a `height`".to_string(),
+            });
+        }
+
+        let rows: Vec<(usize, &str)> = lines.filter(|(_, line)| !line.trim().is_empty()).collect();
+        match height {
+            Some(height) if height != rows.len() => {
+                let line = rows.get(height).or(rows.last()).map_or(header_end, |&(number, _)| number);
+                errors.push(LevelError::BadDimensions {
+                    at: Position::new(line, 1),
+                    message: format!("expected {height} rows, found {}", rows.len()),
+                });
+            }
+            None if rows.len() > BOARD_HEIGHT => {
+                let at = Position::new(rows[rows.len() - BOARD_HEIGHT - 1].0, 1);
+                errors.push(LevelError::TooLarge { at, what: "rows", found: rows.len(), limit: BOARD_HEIGHT });
+            }
+            _ => {}
+        }
+
+        // Rows are bottom aligned, any that don't fit above the well are dropped.
+        // Unknown symbols are reported where they first appear rather than in every cell.
+        let mut unknown_symbols = HashSet::new();
+        let top = BOARD_HEIGHT as isize - rows.len() as isize;
+        for (i, &(number, line)) in rows.iter().enumerate() {
+            let start = indent(line);
+            let row = line.trim();
+            let y = top + i as isize;
+            let found = row.chars().count();
+            if found != width {
+                let column = start + found.min(width);
+                errors.push(LevelError::RaggedRow { at: Position::new(number, column), expected: width, found });
+            }
+            for (x, symbol) in row.chars().enumerate() {
+                let Some(&id) = legend.get(&symbol) else {
+                    if unknown_symbols.insert(symbol) {
+                        let at = Position::new(number, start + x);
+                        errors.push(LevelError::UnknownTile { at, tile: symbol.to_string() });
+                    }
+                    continue;
+                };
+                if x < BOARD_WIDTH && y >= 0 {
+                    level.board.set(x, y as usize, id);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(level)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Load and check a level file, or import a Tiled map
+    pub fn load(path: &Path) -> Result<Self, Vec<LevelError>> {
+        if tiled::is_map(path) {
+            return tiled::load(path).map_err(|err| vec![LevelError::Import(err)]);
+        }
+        let text = fs::read_to_string(path).map_err(|err| vec![LevelError::Io(err)])?;
+        let mut level = Self::parse(&text)?;
+        if level.name.is_empty() {
+            level.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        }
+        Ok(level)
+    }
+
+    /// Puzzles deal a fixed set of pieces and have a goal to reach
+    pub fn is_puzzle(&self) -> bool {
+        self.goal.is_some() && !self.pieces.is_empty()
+    }
+}
+
+/// Every puzzle in the level directory, sorted by file name
+pub fn list_puzzles() -> Vec<(PathBuf, Level)> {
+    list_puzzles_in(Path::new(LEVEL_DIR))
+}
+
+/// Every puzzle in `dir`, sorted by file name
+pub fn list_puzzles_in(dir: &Path) -> Vec<(PathBuf, Level)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        warn!("Could not read the {} directory", dir.display());
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "data") || tiled::is_map(path))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| match Level::load(&path) {
+            Ok(level) => Some((path, level)),
+            Err(errors) => {
+                log_errors(&path, &errors);
+                None
+            }
+        })
+        .filter(|(_, level)| level.is_puzzle())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BOARD_HEIGHT, EMPTY};
+
+    const BOTTOM: i32 = BOARD_HEIGHT as i32 - 1;
+
+    #[test]
+    fn header_makes_a_puzzle() {
+        let level = Level::parse("name: Test\npieces: T i L\nhold: 1\ngoal: tspins 2\n0000000000\n1110111111\n").unwrap();
+        assert_eq!(level.version, 0);
+        assert_eq!(level.name, "Test");
+        assert_eq!(level.pieces, vec![PieceKind::T, PieceKind::I, PieceKind::L]);
+        assert_eq!(level.holds, Some(1));
+        assert_eq!(level.goal, Some(Goal::TSpins(2)));
+        assert!(level.is_puzzle());
+        assert_eq!(level.board.get(0, BOTTOM), Some(1));
+        assert_eq!(level.board.get(3, BOTTOM), Some(EMPTY));
+    }
+
+    #[test]
+    fn plain_grid_is_not_a_puzzle() {
+        let level = Level::parse("0000000000\n1111111110\n").unwrap();
+        assert!(level.pieces.is_empty());
+        assert_eq!(level.holds, None);
+        assert_eq!(level.goal, None);
+        assert!(!level.is_puzzle());
+    }
+
+    const VERSIONED: &str = "version: 1
+name: Legend
+author: Someone
+width: 10
+height: 2
+legend: . = empty
+legend: # = dirt
+legend: T = piece T
+legend: 9 = 9
+---
+..........
+#T#9#####.
+";
+
+    #[test]
+    fn legend_maps_symbols_to_tiles() {
+        let level = Level::parse(VERSIONED).unwrap();
+        assert_eq!(level.version, 1);
+        assert_eq!(level.author, "Someone");
+        assert_eq!(level.board.get(0, BOTTOM), Some(GARBAGE_TILE_ID));
+        assert_eq!(level.board.get(1, BOTTOM), Some(PieceKind::T.tile_id()));
+        assert_eq!(level.board.get(3, BOTTOM), Some(9));
+        assert_eq!(level.board.get(9, BOTTOM), Some(EMPTY));
+        assert_eq!(level.board.get(0, BOTTOM - 1), Some(EMPTY));
+    }
+
+    #[test]
+    fn legend_defines_multi_digit_tiles() {
+        let text = VERSIONED.replace("legend: 9 = 9", "legend: 9 = 120 #ff8000");
+        let level = Level::parse(&text).unwrap();
+        assert_eq!(level.board.get(3, BOTTOM), Some(120));
+        assert_eq!(level.tiles, vec![(120, [255, 128, 0, 255])]);
+    }
+
+    /// Every problem found parsing `text`
+    fn errors(text: &str) -> Vec<LevelError> {
+        let Err(errors) = Level::parse(text) else {
+            panic!("level parsed without errors");
+        };
+        errors
+    }
+
+    /// Line and column of the first problem found parsing `text`
+    fn error_at(text: &str) -> (usize, usize) {
+        let at = errors(text)[0].position().unwrap();
+        (at.line, at.column)
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        // A symbol missing from the legend
+        assert_eq!(error_at(&VERSIONED.replace("#T#9", "#T#x")), (12, 4));
+        // An id past the last tile without a colour to define it
+        assert_eq!(error_at(&VERSIONED.replace("9 = 9", "9 = 99")), (9, 13));
+        assert_eq!(error_at(&VERSIONED.replace("9 = 9", "9 = 99 #12345")), (9, 16));
+        // A colour on a piece tile would recolour every piece of that kind
+        assert_eq!(error_at(&VERSIONED.replace("9 = 9", "9 = 5 #ff8000")), (9, 15));
+        // A misspelt header key
+        assert_eq!(error_at(&VERSIONED.replace("author: Someone", "  gaol: lines 4")), (3, 3));
+        // Rows that are too long, too short, or too few
+        assert_eq!(error_at(&VERSIONED.replace("..........", "..........#")), (11, 11));
+        assert_eq!(error_at(&VERSIONED.replace("..........", ".........")), (11, 10));
+        assert_eq!(error_at(&VERSIONED.replace("height: 2", "height: 3")), (12, 1));
+        // Version 0 digits, with the row's indentation counted
+        assert_eq!(error_at("0000000000\n  11111x1110"), (2, 8));
+        assert_eq!(error_at(&VERSIONED.replace("version: 1", "version: 2")).0, 1);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let text = VERSIONED
+            .replace("width: 10", "width: 8")
+            .replace("height: 2", "height: 41")
+            .replace("..........", "....x.....#")
+            .replace("#T#9##", "#T#?x?");
+        let errors = errors(&text);
+        assert!(matches!(errors[0], LevelError::BadDimensions { .. }));
+        assert!(matches!(errors[1], LevelError::TooLarge { what: "rows", found: 41, .. }));
+        assert!(matches!(errors[2], LevelError::BadDimensions { .. }));
+        assert!(matches!(errors[3], LevelError::RaggedRow { expected: 10, found: 11, .. }));
+        assert!(matches!(&errors[4], LevelError::UnknownTile { tile, .. } if tile == "x"));
+        assert!(matches!(&errors[5], LevelError::UnknownTile { tile, .. } if tile == "?"));
+        assert_eq!(errors.len(), 6);
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        let Err(errors) = Level::load(Path::new("levels/does_not_exist.data")) else {
+            panic!("missing level loaded");
+        };
+        assert!(matches!(errors[..], [LevelError::Io(_)]));
+    }
+
+    #[test]
+    fn bundled_levels_parse() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(LEVEL_DIR);
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(errors) = Level::load(&path) {
+                panic!("{}: {}", path.display(), errors[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn goals_parse() {
+        assert_eq!(Goal::parse("lines 4"), Some(Goal::Lines(4)));
+        assert_eq!(Goal::parse("Perfect-Clear"), Some(Goal::PerfectClear));
+        assert_eq!(Goal::parse("tspins"), None);
+        assert_eq!(Goal::parse("score 100"), None);
+    }
+}
@@ -1,212 +1,895 @@
 // #![deny(clippy::all)]
 // #![forbid(unsafe_code)]
 #![allow(dead_code)]
+mod atlas;
+mod blit;
+mod board;
+mod font;
+mod garbage;
+mod gravity;
+mod headless;
+mod input;
+mod level;
+mod modes;
+mod piece;
+mod randomizer;
+mod rng;
+mod scoring;
+mod settings;
+mod srs;
+mod tiled;
+mod timestep;
+mod tspin;
+
+use atlas::{Atlas, Image, Region, ASSET_DIR};
+use blit::{blit, DrawMode, View};
+use board::{Board, BOARD_WIDTH, EMPTY, HIDDEN_ROWS, VISIBLE_HEIGHT};
+use font::{Align, Font, TextStyle, FONT_FILE};
+use garbage::GARBAGE_TILE_ID;
+use gravity::{gravity_for_level, LOCK_DELAY_FRAMES, MAX_GRAVITY, MAX_LOCK_RESETS, SUBCELLS_PER_CELL};
+use input::{Action, Button, Buttons, InputHandler};
+use level::Level;
+use modes::{GameMode, ModeKind, Status};
+use piece::{ActivePiece, PieceKind, Rotation, Turn};
+use randomizer::Randomizer;
+use scoring::{ClearEvent, DropKind, Scoring, Spin};
+use settings::Settings;
+use timestep::{FixedTimestep, TICKS_PER_SECOND};
+use tspin::Kick;
 use simple_logger::SimpleLogger;
-use log::{debug, error, info, warn, LevelFilter};
+use log::{debug, error, LevelFilter};
 use pixels::{Error, Pixels, SurfaceTexture};
+use std::collections::VecDeque;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
 use std::time::Instant;
 // use winit::dpi::LogicalSize;
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
-use std::fs;
 
 const INTERNAL_WIDTH: u32 = 256;
 const INTERNAL_HEIGHT: u32 = 240;
 const SCALE: u32 = 4;
 const WIN_WIDTH: u32 = INTERNAL_WIDTH * SCALE;
 const WIN_HEIGHT: u32 = INTERNAL_HEIGHT * SCALE;
-const TILE_WIDTH: u32 = 16;
-const TILES_PER_ROW: u32 = INTERNAL_WIDTH / TILE_WIDTH; // By default the total tiles in row would be 16
-
-const TRANSPARENT_SPRITE: Sprite = Sprite {
-    data: [[0, 0, 0, 0]; 256],
-    width: 16,
-    height: 16,
-};
-const DIRT_SPRITE: Sprite = Sprite {
-    data: [[128, 0, 0, 255]; 256],
-    width: 16,
-    height: 16,
-};
-const GRASS_SPRITE: Sprite = Sprite {
-    data: [[0, 255, 0, 255]; 256],
-    width: 16,
-    height: 16,
-};
-const TEST_TILE_TRANSPARENT: Tile = Tile {
-    sprite: TRANSPARENT_SPRITE,
-    id: 0,
-};
-const TEST_TILE_A: Tile = Tile {
-    sprite: DIRT_SPRITE,
-    id: 1,
-};
-const TEST_TILE_B: Tile = Tile {
-    sprite: GRASS_SPRITE,
-    id: 2,
-};
-
-const DEFAULT_MAP_STRING: &str = 
-"1100000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 0000000000000000
- 1111111111111111
- 1111111111111111";
-
-const MAX_FRAME_RATE: f32 = 60.0;
-const MAX_FRAME_TIME: f32 = 1.0 / MAX_FRAME_RATE;
+const BLOCK_SIZE: u32 = 10; // Size of one board cell in pixels, 20 visible rows fit in the 240 pixel frame
+
+// Top left corner of the well, centered in the frame
+const BOARD_X: i32 = (INTERNAL_WIDTH - BOARD_WIDTH as u32 * BLOCK_SIZE) as i32 / 2;
+const BOARD_Y: i32 = (INTERNAL_HEIGHT - VISIBLE_HEIGHT as u32 * BLOCK_SIZE) as i32 / 2;
+const BORDER_COLOR: [u8; 4] = [96, 96, 96, 255];
+
+// The hold slot and next queue are drawn beside the well with smaller blocks
+const PREVIEW_BLOCK_SIZE: u32 = 6;
+const PREVIEW_SLOT_HEIGHT: i32 = 3 * PREVIEW_BLOCK_SIZE as i32;
+const HOLD_X: i32 = BOARD_X - 8 - 4 * PREVIEW_BLOCK_SIZE as i32;
+const QUEUE_X: i32 = BOARD_X + (BOARD_WIDTH as u32 * BLOCK_SIZE) as i32 + 8;
+const PREVIEW_Y: i32 = BOARD_Y + 8;
+const HOLD_USED_COLOR: [u8; 4] = [80, 80, 80, 255];
+
+// Opacity of the ghost piece
+const GHOST_ALPHA: u8 = 72;
+
+// Text colours for the HUD and menus
+const TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+const LABEL_COLOR: [u8; 4] = [160, 160, 160, 255];
+const HIGHLIGHT_COLOR: [u8; 4] = [240, 240, 0, 255];
+const SHADOW_COLOR: [u8; 4] = [48, 48, 48, 255];
+// The HUD is right aligned to the left of the well, below the hold slot, one label and value pair per entry
+const HUD_X: i32 = BOARD_X - 6;
+const HUD_Y: i32 = PREVIEW_Y + PREVIEW_SLOT_HEIGHT + 16;
+const HUD_ENTRY_HEIGHT: i32 = 16;
+// Vertical layout of the menus
+const MENU_TITLE_Y: i32 = 40;
+const MENU_LIST_Y: i32 = 70;
+const MENU_ITEM_HEIGHT: i32 = 10;
+const MENU_FOOTER_Y: i32 = INTERNAL_HEIGHT as i32 - 20;
+
+// Colour of garbage blocks
+const DIRT_COLOR: [u8; 4] = [128, 0, 0, 255];
+// Colour grass falls back to when the block sprites can't be loaded
+const GRASS_COLOR: [u8; 4] = [0, 255, 0, 255];
+
+/// Manifest of the board block sprites, in the asset directory
+const BLOCKS_MANIFEST: &str = "blocks.atlas";
+/// The grey block sprite, tinted for pieces, garbage and the colours levels define
+const BLOCK_SPRITE: &str = "block";
+/// Block sprites for the level tiles by tile id, with the colour to tint them or `None` to draw them as they
+/// are. Piece tiles follow, each tinting the block with its piece colour.
+const LEVEL_TILE_SPRITES: [(&str, Option<[u8; 4]>); 3] =
+    [("empty", None), (BLOCK_SPRITE, Some(DIRT_COLOR)), ("grass", None)];
+
+const KEY_BINDINGS: [(VirtualKeyCode, Button); 11] = [
+    (VirtualKeyCode::Left, Button::Left),
+    (VirtualKeyCode::Right, Button::Right),
+    (VirtualKeyCode::Down, Button::SoftDrop),
+    (VirtualKeyCode::Space, Button::HardDrop),
+    (VirtualKeyCode::V, Button::SonicDrop),
+    (VirtualKeyCode::Up, Button::RotateClockwise),
+    (VirtualKeyCode::X, Button::RotateClockwise),
+    (VirtualKeyCode::Z, Button::RotateCounterClockwise),
+    (VirtualKeyCode::A, Button::Rotate180),
+    (VirtualKeyCode::C, Button::Hold),
+    (VirtualKeyCode::LShift, Button::Hold),
+];
 
 struct World {
+    /// Tile definitions, indexed by tile id
     tiles: Vec<Tile>,
+    board: Board,
+    piece: Option<ActivePiece>,
+    /// Where new pieces come from, `None` when only the pieces already queued are dealt
+    randomizer: Option<Box<dyn Randomizer>>,
+    /// Upcoming pieces, the front is spawned next
+    queue: VecDeque<PieceKind>,
+    hold: Option<PieceKind>,
+    /// Hold can only be used once until the next piece locks
+    hold_used: bool,
+    /// Times hold may still be used, `None` for no limit
+    holds_left: Option<u32>,
+    settings: Settings,
+    /// The piece tiles faded for drawing the ghost piece, in `PieceKind::ALL` order
+    ghosts: [Tile; 7],
+    /// Blocks for the hold slot and next queue, in `PieceKind::ALL` order
+    previews: [Sprite; 7],
+    /// Block for the hold slot while it can't be used
+    used_hold_preview: Sprite,
+    scoring: Scoring,
+    /// Clears from the last update, for the HUD, statistics and game modes to react to
+    clear_events: Vec<ClearEvent>,
+    /// Number of updates since the game started
+    frame: u64,
+    level: u32,
+    /// Progress towards the next row the active piece falls, in subcells
+    gravity_progress: u32,
+    /// Frames the active piece has spent on the ground
+    lock_timer: u32,
+    /// Times the lock delay has been restarted by moving the active piece
+    lock_resets: u32,
+    /// Lowest row the active piece has reached, reaching a new one gives back the lock resets
+    lowest_row: i32,
+    /// The last rotation and the kick it used, `None` once the piece has moved since, for T-spin detection
+    last_kick: Option<Kick>,
+    /// Gravity to use instead of the level's, in subcells per frame
+    gravity_override: Option<u32>,
+    /// Soft drop is held this tick
+    soft_drop: bool,
+    /// Frames left before the next piece spawns
+    entry_delay_timer: u32,
+    game_over: bool,
 }
 impl World {
     /// Create a new `World` with it's sprite map
-    fn new() -> Self {
-        let mut tiles = Vec::new();
-        Self { tiles }
-    }
-    /// Update the `World` internal state
-    fn update(&mut self) {
-        // Move Piece down
-        // Check if piece can move down
-        
-        
-        // If it can't, then lock the piece
-        // Check if any rows are full
-        // If they are, then remove them
+    fn new(tiles: Vec<Tile>, board: Board, settings: Settings) -> Self {
+        let ghosts = PieceKind::ALL.map(|kind| {
+            let tile = &tiles[kind.tile_id() as usize];
+            Tile {
+                sprite: tile.sprite.translucent(GHOST_ALPHA),
+                id: tile.id,
+            }
+        });
+        let mut world = Self {
+            tiles,
+            board,
+            piece: None,
+            randomizer: Some(settings.randomizer.build(settings.seed.unwrap_or_else(settings::clock_seed))),
+            queue: VecDeque::new(),
+            hold: None,
+            hold_used: false,
+            holds_left: None,
+            settings,
+            ghosts,
+            previews: PieceKind::ALL.map(|kind| Sprite::solid(kind.color(), PREVIEW_BLOCK_SIZE)),
+            used_hold_preview: Sprite::solid(HOLD_USED_COLOR, PREVIEW_BLOCK_SIZE),
+            scoring: Scoring::new(),
+            clear_events: Vec::new(),
+            frame: 0,
+            level: 1,
+            gravity_progress: 0,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_row: 0,
+            last_kick: None,
+            gravity_override: None,
+            soft_drop: false,
+            entry_delay_timer: 0,
+            game_over: false,
+        };
+        world.spawn_piece();
+        world
+    }
+    /// Update the `World` internal state by one tick, applying the player's actions first
+    fn update(&mut self, actions: &[Action]) {
+        self.frame += 1;
+        self.clear_events.clear();
+        for &action in actions {
+            self.apply(action);
+        }
 
-        return;
+        let soft_drop = std::mem::take(&mut self.soft_drop);
+        if self.game_over {
+            return;
+        }
+
+        // Wait out the entry delay before the next piece appears
+        if self.piece.is_none() {
+            self.entry_delay_timer = self.entry_delay_timer.saturating_sub(1);
+            if self.entry_delay_timer == 0 {
+                self.spawn_piece();
+            }
+            return;
+        }
+
+        // Move Piece down one row for every whole cell of gravity built up, soft drop speeds gravity up
+        // but is never slower than it is at level 1, so it still works without gravity
+        let mut gravity = self.gravity();
+        if soft_drop {
+            let soft_gravity = gravity.max(gravity_for_level(1)) * self.settings.soft_drop_factor;
+            gravity = soft_gravity.clamp(gravity, MAX_GRAVITY);
+        }
+        self.gravity_progress += gravity;
+        while self.gravity_progress >= SUBCELLS_PER_CELL {
+            self.gravity_progress -= SUBCELLS_PER_CELL;
+            if !self.try_move(0, 1) {
+                self.gravity_progress = 0;
+                break;
+            }
+            if soft_drop {
+                self.scoring.drop(DropKind::Soft, 1);
+            }
+        }
+
+        // If it can't move down, then lock the piece once the lock delay runs out
+        if self.is_grounded() {
+            self.lock_timer += 1;
+            if self.lock_timer >= LOCK_DELAY_FRAMES {
+                self.lock_piece();
+            }
+        } else {
+            self.lock_timer = 0;
+        }
     }
 
-    fn move_piece_right(&mut self) {
-        return;
+    /// Subcells a frame the active piece falls without soft drop
+    fn gravity(&self) -> u32 {
+        self.gravity_override.unwrap_or_else(|| gravity_for_level(self.level))
     }
 
-    fn move_piece_left(&mut self) {
-        return;
+    /// Pixels the active piece is drawn below its row, `alpha` of the way to where gravity takes it next tick
+    fn fall_offset(&self, alpha: f32) -> i32 {
+        let Some(piece) = &self.piece else {
+            return 0;
+        };
+        let rows = ((self.gravity_progress + self.gravity()) / SUBCELLS_PER_CELL) as i32;
+        let rows = rows.min(self.landing_position(piece).y - piece.y);
+        (rows as f32 * alpha * BLOCK_SIZE as f32) as i32
     }
 
-    fn move_piece_down(&mut self) {
-        return;
+    /// True if the active piece is resting on the stack or the floor
+    fn is_grounded(&self) -> bool {
+        self.piece
+            .is_some_and(|piece| !self.board.fits(piece.shifted(0, 1).board_cells()))
     }
 
-    fn rotate_piece(&mut self) {
-        return;
+    /// Lock delay bookkeeping after the active piece moves or rotates.
+    ///
+    /// Reaching a new lowest row gives back all the resets, any other move while the lock delay is running
+    /// restarts it until the resets run out.
+    fn piece_moved(&mut self) {
+        let Some(piece) = self.piece else {
+            return;
+        };
+        if piece.y > self.lowest_row {
+            self.lowest_row = piece.y;
+            self.lock_resets = 0;
+            self.lock_timer = 0;
+        } else if self.lock_timer > 0 && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_resets += 1;
+            self.lock_timer = 0;
+        }
     }
 
-    /// Draw the `World` state to the frame buffer.
+    /// Move the active piece by `(dx, dy)` if nothing is in the way. Returns whether it moved.
+    fn try_move(&mut self, dx: i32, dy: i32) -> bool {
+        let Some(piece) = self.piece else {
+            return false;
+        };
+        let moved = piece.shifted(dx, dy);
+        if !self.board.fits(moved.board_cells()) {
+            return false;
+        }
+        self.piece = Some(moved);
+        self.last_kick = None;
+        self.piece_moved();
+        true
+    }
+
+    /// Where `piece` would land if it dropped straight down
+    fn landing_position(&self, piece: &ActivePiece) -> ActivePiece {
+        let mut landed = *piece;
+        while self.board.fits(landed.shifted(0, 1).board_cells()) {
+            landed = landed.shifted(0, 1);
+        }
+        landed
+    }
+
+    /// Write the active piece into the board, clear any full rows and spawn the next piece
+    fn lock_piece(&mut self) {
+        let mut spin = Spin::None;
+        if let Some(piece) = self.piece.take() {
+            spin = tspin::detect(&self.board, &piece, self.last_kick);
+            self.board.lock(piece.board_cells(), piece.tile_id());
+        }
+        let cleared = self.board.clear_lines();
+        debug!("Cleared {} lines, spin {:?}", cleared, spin);
+        let perfect_clear = cleared > 0 && self.board.is_empty();
+        if let Some(event) = self.scoring.lock(cleared as u32, spin, perfect_clear, self.level) {
+            self.clear_events.push(event);
+        }
+        self.hold_used = false;
+        self.entry_delay_timer = self.settings.entry_delay;
+        if self.entry_delay_timer == 0 {
+            self.spawn_piece();
+        }
+    }
+
+    /// Take the next piece from the queue, topping the queue back up from the randomizer.
+    /// Returns `None` once a fixed sequence runs out.
+    fn next_piece(&mut self) -> Option<PieceKind> {
+        if let Some(randomizer) = &mut self.randomizer {
+            while self.queue.len() <= self.settings.preview_count {
+                self.queue.push_back(randomizer.next());
+            }
+        }
+        self.queue.pop_front()
+    }
+
+    /// Place the next piece at the top of the well, if there is one
+    fn spawn_piece(&mut self) {
+        if let Some(kind) = self.next_piece() {
+            self.spawn_kind(kind);
+        }
+    }
+
+    /// Add the tiles a level defines, so its board can be drawn. Each tints the block garbage is drawn with.
+    fn define_tiles(&mut self, tiles: &[(u8, [u8; 4])]) {
+        let block = self.tiles[GARBAGE_TILE_ID as usize].sprite.clone();
+        for &(id, color) in tiles {
+            let index = id as usize;
+            if self.tiles.len() <= index {
+                self.tiles.resize(index + 1, Tile::transparent(EMPTY));
+            }
+            self.tiles[index] = Tile {
+                sprite: block.tinted(color),
+                id,
+            };
+        }
+    }
+
+    /// Replace the randomizer with a fixed sequence of pieces and a limited number of holds
+    fn deal(&mut self, pieces: &[PieceKind], holds: Option<u32>) {
+        self.randomizer = None;
+        self.queue = pieces.iter().copied().collect();
+        self.hold = None;
+        self.hold_used = false;
+        self.holds_left = holds;
+        self.piece = None;
+        self.spawn_piece();
+    }
+
+    /// Put the active piece back at the spawn position after the board has been replaced under it, ending the
+    /// game if there is no room for it there
+    fn respawn_piece(&mut self) {
+        if let Some(piece) = self.piece.take() {
+            self.spawn_kind(piece.kind);
+        }
+    }
+
+    /// True once a fixed sequence has been dealt out and the last piece has locked
+    fn out_of_pieces(&self) -> bool {
+        self.randomizer.is_none() && self.piece.is_none() && self.queue.is_empty()
+    }
+
+    /// Place a piece of `kind` at the top of the well, ending the game if there is no room for it
+    fn spawn_kind(&mut self, kind: PieceKind) {
+        let piece = ActivePiece::spawn(kind);
+        self.gravity_progress = 0;
+        self.lock_timer = 0;
+        self.lock_resets = 0;
+        self.lowest_row = piece.y;
+        self.last_kick = None;
+        if self.board.fits(piece.board_cells()) {
+            self.piece = Some(piece);
+        } else {
+            // Keep the piece at the front of the queue in case the mode lets play go on
+            self.queue.push_front(kind);
+            self.game_over = true;
+        }
+    }
+
+    /// Apply an action from the input handler
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Shift(cells) => self.shift_piece(cells),
+            Action::SoftDrop => self.soft_drop = true,
+            Action::HardDrop => self.hard_drop(),
+            Action::SonicDrop => self.sonic_drop(),
+            Action::Rotate(turn) => self.rotate_piece(turn),
+            Action::Hold => self.hold_piece(),
+        }
+    }
+
+    /// Move the active piece sideways by up to `cells`, negative is left, stopping at the first obstruction
+    fn shift_piece(&mut self, cells: i32) {
+        for _ in 0..cells.abs() {
+            if !self.try_move(cells.signum(), 0) {
+                break;
+            }
+        }
+    }
+
+    /// Drop the active piece straight to the bottom and lock it at once
+    fn hard_drop(&mut self) {
+        let Some(piece) = self.piece else {
+            return;
+        };
+        let landed = self.landing_position(&piece);
+        self.scoring.drop(DropKind::Hard, (landed.y - piece.y) as u32);
+        if landed.y != piece.y {
+            self.last_kick = None;
+        }
+        self.piece = Some(landed);
+        self.lock_piece();
+    }
+
+    /// Drop the active piece straight to the bottom, leaving it free to move until the lock delay runs out
+    fn sonic_drop(&mut self) {
+        let Some(piece) = self.piece else {
+            return;
+        };
+        let landed = self.landing_position(&piece);
+        self.scoring.drop(DropKind::Soft, (landed.y - piece.y) as u32);
+        if landed.y != piece.y {
+            self.last_kick = None;
+        }
+        self.piece = Some(landed);
+        self.piece_moved();
+    }
+
+    /// Swap the active piece with the held one, or with the next piece if nothing is held yet
+    fn hold_piece(&mut self) {
+        if self.hold_used || self.holds_left == Some(0) {
+            return;
+        }
+        let Some(piece) = self.piece else {
+            return;
+        };
+        let Some(swap) = self.hold.or_else(|| self.next_piece()) else {
+            return;
+        };
+
+        self.hold = Some(piece.kind);
+        self.hold_used = true;
+        if let Some(holds) = &mut self.holds_left {
+            *holds -= 1;
+        }
+        self.spawn_kind(swap);
+    }
+
+    /// Rotate the active piece, using the SRS wall kicks if it is obstructed
+    fn rotate_piece(&mut self, turn: Turn) {
+        let Some(piece) = self.piece else {
+            return;
+        };
+        if let Some((rotated, index)) = srs::rotate(&self.board, &piece, piece.rotation.turned(turn)) {
+            self.piece = Some(rotated);
+            self.last_kick = Some(Kick { turn, index });
+            self.piece_moved();
+        }
+    }
+
+    /// Draw the `World` state to the frame buffer, `alpha` of the way to the next tick.
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    fn draw(&self, frame: &mut [u8], camera_offset: (usize, usize)) {
+    fn draw(&self, frame: &mut [u8], alpha: f32) {
         // Clear frame buffer
         for pixel in frame.chunks_exact_mut(4) {
             pixel.copy_from_slice(&[0, 0, 0, 255]);
         }
 
-        // Draw a white border around the screen
-        let draw_border = false;
-        if draw_border{
-            for x in 0..INTERNAL_WIDTH {
-                for y in 0..INTERNAL_HEIGHT {
-                    if x == 0 || x == INTERNAL_WIDTH - 1 || y == 0 || y == INTERNAL_HEIGHT - 1 {
-                        let pixel_index = (y * INTERNAL_WIDTH + x) as usize * 4 as usize;
-                        frame[pixel_index..pixel_index + 4].copy_from_slice(&[255, 255, 255, 255]);
-                    }
+        // Draw a border around the well
+        let left = BOARD_X - 1;
+        let right = BOARD_X + (BOARD_WIDTH as u32 * BLOCK_SIZE) as i32;
+        let top = BOARD_Y - 1;
+        let bottom = BOARD_Y + (VISIBLE_HEIGHT as u32 * BLOCK_SIZE) as i32;
+        for x in left..=right {
+            for y in top..=bottom {
+                if x == left || x == right || y == top || y == bottom {
+                    let pixel_index = (y * INTERNAL_WIDTH as i32 + x) as usize * 4;
+                    frame[pixel_index..pixel_index + 4].copy_from_slice(&BORDER_COLOR);
+                }
+            }
+        }
+
+        // Draw locked cells
+        let origin = (BOARD_X, BOARD_Y);
+        for (row, cells) in self.board.visible_rows().enumerate() {
+            for (column, &id) in cells.iter().enumerate() {
+                if id != EMPTY {
+                    self.tiles[id as usize].draw(frame, column as i32, row as i32, origin);
+                }
+            }
+        }
+
+        // Draw the ghost piece underneath the active piece
+        if let (true, Some(piece)) = (self.settings.ghost, &self.piece) {
+            let ghost = &self.ghosts[piece.kind as usize];
+            for (x, y) in self.landing_position(piece).board_cells() {
+                let row = y - HIDDEN_ROWS as i32;
+                if row >= 0 {
+                    ghost.draw(frame, x, row, origin);
+                }
+            }
+        }
+
+        // Draw the active piece, partway to where it falls next
+        if let Some(piece) = &self.piece {
+            let falling = (BOARD_X, BOARD_Y + self.fall_offset(alpha));
+            for (x, y) in piece.board_cells() {
+                let row = y - HIDDEN_ROWS as i32;
+                if row >= 0 {
+                    self.tiles[piece.tile_id() as usize].draw(frame, x, row, falling);
                 }
             }
         }
 
-        // Draw tiles
-        for (i, tile) in self.tiles.iter().enumerate() {
-            tile.draw(frame, i, camera_offset);
+        // Draw the hold slot, greyed out while it can't be used
+        if let Some(kind) = self.hold {
+            let used = self.hold_used || self.holds_left == Some(0);
+            let sprite = if used { &self.used_hold_preview } else { &self.previews[kind as usize] };
+            draw_preview(frame, kind, sprite, (HOLD_X, PREVIEW_Y));
+        }
+
+        // Draw the next queue
+        for (i, &kind) in self.queue.iter().take(self.settings.preview_count).enumerate() {
+            let y = PREVIEW_Y + i as i32 * (PREVIEW_SLOT_HEIGHT + PREVIEW_BLOCK_SIZE as i32);
+            draw_preview(frame, kind, &self.previews[kind as usize], (QUEUE_X, y));
         }
     }
 }
 
-// Derive copy
-#[derive(Copy, Clone)]
+/// Draw a small copy of a piece in its spawn orientation out of `sprite` blocks, with its top left corner at
+/// `origin`
+fn draw_preview(frame: &mut [u8], kind: PieceKind, sprite: &Sprite, origin: (i32, i32)) {
+    for (x, y) in kind.cells(Rotation::Spawn) {
+        sprite.draw(
+            frame,
+            origin.0 + x * PREVIEW_BLOCK_SIZE as i32,
+            origin.1 + y * PREVIEW_BLOCK_SIZE as i32,
+        );
+    }
+}
+
+/// Tile definitions, indexed by the ids used in the map data: the level tiles followed by one tile per piece
+/// kind, see `PieceKind::tile_id`. Uses the block sprite sheet, or plain colours if it can't be loaded.
+fn load_tiles() -> Vec<Tile> {
+    let path = Path::new(ASSET_DIR).join(BLOCKS_MANIFEST);
+    let atlas = Atlas::load_manifest(&path)
+        .map_err(|err| error!("Loading {} failed: {err}", path.display()))
+        .ok();
+
+    let piece_sprites = PieceKind::ALL.map(|kind| (BLOCK_SPRITE, Some(kind.color())));
+    LEVEL_TILE_SPRITES
+        .into_iter()
+        .chain(piece_sprites)
+        .enumerate()
+        .map(|(id, (name, tint))| {
+            let sprite = atlas.as_ref().and_then(|atlas| {
+                let sprite = Sprite::from_atlas(atlas, name);
+                if sprite.is_none() {
+                    error!("{} has no sprite called '{name}'", path.display());
+                }
+                sprite
+            });
+            let sprite = sprite.unwrap_or_else(|| {
+                let color = match name {
+                    "empty" => [0, 0, 0, 0],
+                    "grass" => GRASS_COLOR,
+                    _ => [255; 4],
+                };
+                Sprite::solid(color, BLOCK_SIZE)
+            });
+            Tile {
+                sprite: match tint {
+                    Some(color) => sprite.tinted(color),
+                    None => sprite,
+                },
+                id: id as u8,
+            }
+        })
+        .collect()
+}
+
+/// The HUD and menu font, or one that draws nothing if it can't be loaded
+fn load_font() -> Font {
+    let path = Path::new(ASSET_DIR).join(FONT_FILE);
+    Font::load_fnt(&path).unwrap_or_else(|err| {
+        error!("Loading {} failed: {err}", path.display());
+        Font::new(Image::filled(0, 0, [0; 4]), 0)
+    })
+}
+
+#[derive(Clone)]
 struct Tile {
     sprite: Sprite,
     id: u8,
 }
 impl Tile {
-    fn draw(&self, frame: &mut [u8], tile_index: usize, camera_offset: (usize, usize)) {
-        let current_column = tile_index as u32 % TILES_PER_ROW;
-
-        let current_row = tile_index as u32 / TILES_PER_ROW;
-
-        let offset_x: i32 = (current_column * TILE_WIDTH) as i32 - camera_offset.0 as i32;
-        let offset_y: i32 = (current_row * TILE_WIDTH) as i32 - camera_offset.1 as i32;
-        
-        // I.e. if tile index is 36, then 36 / 32 = 1, so the tile is in the 1st row
-        warn!("Current tile number: {}", tile_index);
-        warn!("Tile ID: {}", self.id);
-        warn!("Current row: {}", current_row);
-        warn!("Current column: {}", current_column);
-        self.sprite
-            .draw(frame, offset_x as i32, offset_y as i32);
+    /// A tile that draws nothing
+    fn transparent(id: u8) -> Self {
+        Self {
+            sprite: Sprite::solid([0, 0, 0, 0], BLOCK_SIZE),
+            id,
+        }
+    }
+
+    /// Draw the tile at a cell of a grid whose top left corner is at `origin`
+    fn draw(&self, frame: &mut [u8], column: i32, row: i32, origin: (i32, i32)) {
+        let offset_x: i32 = column * BLOCK_SIZE as i32 + origin.0;
+        let offset_y: i32 = row * BLOCK_SIZE as i32 + origin.1;
+
+        self.sprite.draw(frame, offset_x, offset_y);
     }
 }
 
-#[derive(Copy, Clone)]
+/// A region of a shared sprite sheet image, of any size
+#[derive(Clone)]
 struct Sprite {
-    data: [[u8; 4]; 256],
-    width: usize,
-    height: usize,
+    image: Rc<Image>,
+    region: Region,
+    /// Every pixel is fully opaque, so alpha blending can copy the sprite instead
+    opaque: bool,
+    mode: DrawMode,
 }
 impl Sprite {
-    fn draw(&self, frame: &mut [u8], anchor_x: i32, anchor_y: i32) {
-        // Loop through each pixel in the sprite
-        let mut pixel_row: i32 = 0;
-        for (i, pixel) in self.data.iter().enumerate() {
-            // First we set the current pixel x coordinate to the current index of the pixel in the sprite data
-            let mut current_pixel_x: i32 = i as i32;
-            
-            // We see if the current_pixel_x is greater than or equal to the width of the sprite
-            if current_pixel_x >= self.width as i32 {
-                // Pixel row will be equal to the number of times the width of the sprite can go into the current_pixel_x without going over
-                pixel_row = current_pixel_x / self.width as i32;
-
-                // If it is, then we need to reset the current_pixel_x to the remainder of the current_pixel_x divided by the width of the sprite
-                current_pixel_x = current_pixel_x % 16;
+    fn new(image: Rc<Image>, region: Region) -> Self {
+        let mut sprite = Self {
+            image,
+            region,
+            opaque: false,
+            mode: DrawMode::Alpha,
+        };
+        sprite.opaque = sprite.view().is_opaque();
+        sprite
+    }
+
+    /// A square block sprite of `size` pixels filled with a single colour
+    fn solid(color: [u8; 4], size: u32) -> Self {
+        let size = size as usize;
+        Self::new(Rc::new(Image::filled(size, size, color)), Region::new(0, 0, size, size))
+    }
+
+    /// The sprite called `name` in `atlas`
+    fn from_atlas(atlas: &Atlas, name: &str) -> Option<Self> {
+        Some(Self::new(Rc::clone(&atlas.image), atlas.get(name)?))
+    }
+
+    /// The sprite's pixels, for the blitter
+    fn view(&self) -> View<'_> {
+        View {
+            pixels: &self.image.pixels,
+            stride: self.image.width,
+            x: self.region.x,
+            y: self.region.y,
+            width: self.region.width,
+            height: self.region.height,
+        }
+    }
+
+    /// Draw the sprite with its top left corner at `(x, y)`, clipped to the frame
+    fn draw(&self, frame: &mut [u8], x: i32, y: i32) {
+        let mode = if self.opaque && self.mode == DrawMode::Alpha { &DrawMode::Copy } else { &self.mode };
+        blit(frame, INTERNAL_WIDTH as usize, self.view(), (x, y), mode);
+    }
+
+    /// A copy of the sprite drawn with `mode`
+    fn with_mode(&self, mode: DrawMode) -> Self {
+        Self { mode, ..self.clone() }
+    }
+
+    /// A copy of the sprite with its colours multiplied by `color`, still drawn from the same region
+    fn tinted(&self, color: [u8; 4]) -> Self {
+        self.with_mode(DrawMode::Tint(color))
+    }
+
+    /// A copy of the sprite with every pixel's alpha scaled by `alpha / 255`
+    fn translucent(&self, alpha: u8) -> Self {
+        let Region { width, height, .. } = self.region;
+        let mut image = Image::filled(width, height, [0, 0, 0, 0]);
+        for y in 0..height {
+            for x in 0..width {
+                let mut pixel = self.image.pixel(self.region.x + x, self.region.y + y);
+                pixel[3] = (pixel[3] as u16 * alpha as u16 / 255) as u8;
+                let i = (y * width + x) * 4;
+                image.pixels[i..i + 4].copy_from_slice(&pixel);
             }
-            
-            let mut current_pixel_y = pixel_row;
+        }
+        Self::new(Rc::new(image), Region::new(0, 0, width, height)).with_mode(self.mode.clone())
+    }
+}
+
+/// A game in progress: the world and the mode running it
+struct Game {
+    world: World,
+    mode: Box<dyn GameMode>,
+    status: Status,
+}
+
+impl Game {
+    fn new(kind: ModeKind, tiles: Vec<Tile>, settings: Settings) -> Self {
+        let mode = kind.build(&settings);
+        Self::with_mode(mode, tiles, settings)
+    }
+
+    /// A game run by `mode` rather than one built from the settings
+    fn with_mode(mut mode: Box<dyn GameMode>, tiles: Vec<Tile>, settings: Settings) -> Self {
+        let mut world = World::new(tiles, Board::new(), settings);
+        mode.start(&mut world);
+        Self {
+            world,
+            mode,
+            status: Status::Playing,
+        }
+    }
+
+    /// Advance the game by one tick
+    fn update(&mut self, actions: &[Action]) {
+        if self.status != Status::Playing {
+            return;
+        }
+        self.world.update(actions);
+        self.status = self.mode.update(&mut self.world);
+    }
+
+    /// Draw the world `alpha` of the way to the next tick, with the mode's HUD beside the well, and how the game
+    /// ended over it
+    fn draw(&self, frame: &mut [u8], font: &Font, alpha: f32) {
+        self.world.draw(frame, alpha);
+
+        let label = TextStyle::new(LABEL_COLOR);
+        font.draw(frame, "Hold", (HOLD_X, BOARD_Y), &label);
+        font.draw(frame, "Next", (QUEUE_X, BOARD_Y), &label);
+
+        let label = label.align(Align::Right);
+        let value = TextStyle::new(TEXT_COLOR).align(Align::Right).shadow(SHADOW_COLOR);
+        font.draw(frame, self.mode.name(), (HUD_X, HUD_Y), &TextStyle { color: HIGHLIGHT_COLOR, ..value });
+        for (i, (name, text)) in self.mode.hud(&self.world).iter().enumerate() {
+            let y = HUD_Y + (i as i32 + 1) * HUD_ENTRY_HEIGHT;
+            font.draw(frame, name, (HUD_X, y), &label);
+            font.draw(frame, text, (HUD_X, y + font.line_height), &value);
+        }
+
+        let message = match self.status {
+            Status::Playing => return,
+            Status::Cleared => "Cleared!",
+            Status::ToppedOut => "Game Over",
+            Status::OutOfPieces => "Out of pieces",
+        };
+        let center_x = BOARD_X + (BOARD_WIDTH as u32 * BLOCK_SIZE) as i32 / 2;
+        let center_y = BOARD_Y + (VISIBLE_HEIGHT as u32 * BLOCK_SIZE) as i32 / 2;
+        let style = TextStyle::new(HIGHLIGHT_COLOR).align(Align::Center).shadow(SHADOW_COLOR);
+        font.draw(frame, message, (center_x, center_y - font.line_height), &style);
+        font.draw(frame, "Enter for menu", (center_x, center_y + 2), &TextStyle { color: TEXT_COLOR, ..style });
+    }
+
+    /// Window title naming the mode
+    fn title(&self) -> String {
+        format!("Bit World - {}", self.mode.name())
+    }
+}
+
+/// Draw a menu screen: a title, a list with the selected item marked and a line of key hints
+fn draw_menu(frame: &mut [u8], font: &Font, title: &str, items: &[&str], selected: usize, footer: &str) {
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&[0, 0, 0, 255]);
+    }
 
-            let next_pixel = (current_pixel_x + anchor_x, current_pixel_y + anchor_y);
+    let center_x = INTERNAL_WIDTH as i32 / 2;
+    let centered = TextStyle::new(TEXT_COLOR).align(Align::Center).shadow(SHADOW_COLOR);
+    font.draw(frame, "BIT WORLD", (center_x, MENU_TITLE_Y - 2 * font.line_height), &centered);
+    font.draw(frame, title, (center_x, MENU_TITLE_Y), &TextStyle { color: LABEL_COLOR, ..centered });
+    for (i, item) in items.iter().enumerate() {
+        let y = MENU_LIST_Y + i as i32 * MENU_ITEM_HEIGHT;
+        if i == selected {
+            let marker_x = center_x - font.measure(item) / 2 - 2 * font.measure("→");
+            font.draw(frame, "→", (marker_x, y), &TextStyle::new(HIGHLIGHT_COLOR));
+            font.draw(frame, item, (center_x, y), &TextStyle { color: HIGHLIGHT_COLOR, ..centered });
+        } else {
+            font.draw(frame, item, (center_x, y), &centered);
+        }
+    }
+    font.draw(frame, footer, (center_x, MENU_FOOTER_Y), &TextStyle::new(LABEL_COLOR).align(Align::Center));
+}
 
-            // Now we just need to find the index of the pixel in the frame buffer that would be at the location of the next_pixel tuple
-            // The frame array is a 1d array, so we need to convert the 2d coordinates of the next_pixel tuple into a 1d index
-            // The formula for this is: (y * width + x) * 4
-            // Where y is the y coordinate of the pixel, x is the x coordinate of the pixel, and 4 is the number of bytes per pixel
-            let mut pixel_index = (next_pixel.1 * INTERNAL_WIDTH as i32 + next_pixel.0) * 4;
+/// Draw the mode select screen
+fn draw_mode_menu(frame: &mut [u8], font: &Font, selected: usize) {
+    let names = ModeKind::ALL.map(ModeKind::name);
+    draw_menu(frame, font, "Select mode", &names, selected, "↑↓ choose · Enter start · Esc quit");
+}
 
+/// Draw the list of puzzles in the level directory
+fn draw_puzzle_menu(frame: &mut [u8], font: &Font, puzzles: &[(PathBuf, Level)], selected: usize) {
+    if puzzles.is_empty() {
+        let title = format!("No puzzles found in {}/", level::LEVEL_DIR);
+        draw_menu(frame, font, &title, &[], 0, "Esc back");
+        return;
+    }
+    let names: Vec<&str> = puzzles.iter().map(|(_, puzzle)| puzzle.name.as_str()).collect();
+    draw_menu(frame, font, "Select puzzle", &names, selected, "↑↓ choose · Enter play · Esc back");
+}
 
-            // Now we can draw the pixel to the frame buffer, as long as the pixel_index is within the bounds of the frame buffer
-            if pixel_index + 4 <= frame.len() as i32 && pixel_index >= 0{
-                frame[pixel_index as usize..pixel_index as usize + 4].copy_from_slice(pixel);
+/// Check the level file at `path`, printing every problem found. Returns the process exit code.
+fn check_level(path: &Path) -> i32 {
+    match Level::load(path) {
+        Ok(level) => {
+            println!("{}: ok, \"{}\" version {}", path.display(), level.name, level.version);
+            0
+        }
+        Err(errors) => {
+            for err in &errors {
+                eprintln!("{}: {err}", path.display());
             }
+            eprintln!("{}: {} problem(s) found", path.display(), errors.len());
+            1
+        }
+    }
+}
+
+/// Render the menus and a few games without a window and save them as PNGs in `dir`. Returns the process
+/// exit code.
+fn screenshots(dir: &Path) -> i32 {
+    match headless::write_screenshots(dir) {
+        Ok(paths) => {
+            for path in paths {
+                println!("{}", path.display());
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("{}: {err}", dir.display());
+            1
         }
     }
 }
 
 // Starts the main loop of the game
 fn main() -> Result<(), Error> {
+    // `--check-level <path>` checks a level file and `--screenshots <dir>` saves snapshots of the game, both
+    // exit instead of starting the game
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = args.first().map(String::as_str);
+    if let Some(flag @ ("--check-level" | "--screenshots")) = command {
+        let code = match (flag, args.get(1)) {
+            ("--check-level", Some(path)) => check_level(Path::new(path)),
+            ("--screenshots", Some(dir)) => screenshots(Path::new(dir)),
+            _ => {
+                eprintln!("usage: bit_game --check-level <path> | --screenshots <dir>");
+                2
+            }
+        };
+        process::exit(code);
+    }
+
     SimpleLogger::new()
     .with_level(LevelFilter::Warn)
     .init().unwrap();
+    dotenv::dotenv().ok();
+    let mut settings = Settings::from_env();
+    let mut input_handler = InputHandler::new(settings.das, settings.arr);
     // Create Event Loop
     let event_loop = EventLoop::new();
 
@@ -233,47 +916,27 @@ fn main() -> Result<(), Error> {
         Pixels::new(INTERNAL_WIDTH, INTERNAL_HEIGHT, surface_texture)?
     };
 
-    // Create an empty Vector of Tiles
-    let mut tiles: Vec<Tile> = Vec::new();
-    tiles.push(TEST_TILE_TRANSPARENT);
-    tiles.push(TEST_TILE_A);
-    tiles.push(TEST_TILE_B);
-
-
-    // Create new game world from struct
-    let mut world = World::new();
-
-    // Load the map data from levels/level_00.data, read this as a result
-    let map_string = fs::read_to_string("levels/level_00.data").unwrap_or(DEFAULT_MAP_STRING.to_string()).trim().to_string().replace("\n", "");
-
-
-    let map_string: String = map_string.chars().filter(|c| !c.is_whitespace()).collect();
-    // Print map_string 
-    warn!("Map string: {}", map_string);
+    let tiles = load_tiles();
+    let font = load_font();
 
-    // Iterate through string by character
-    for c in map_string.chars() {
-        // Convert character to u8
-        let tile_id = c.to_digit(10).unwrap_or(0) as u8;
+    // Start on the mode select, with the configured mode highlighted
+    let mut selected = ModeKind::ALL.iter().position(|&kind| kind == settings.mode).unwrap_or(0);
+    // Puzzles found in the level directory while the puzzle select is open
+    let mut puzzles: Option<Vec<(PathBuf, Level)>> = None;
+    let mut selected_puzzle = 0;
+    let mut game: Option<Game> = None;
+    let mut title = String::new();
 
-        // Add tile to world
-        world.tiles.push(tiles[tile_id as usize]);
-    }
-
-    let mut right_pressed = false;
-    let mut left_pressed = false;
-    let mut up_pressed = false;
-    let mut down_pressed = false;
-    let mut camera_offset: (usize, usize) = (0, 0);
+    let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
     event_loop.run(move |event, _, control_flow| {
-        // Only allow loop to run at 144 fps
-        *control_flow = ControlFlow::WaitUntil(
-            Instant::now() + std::time::Duration::from_secs_f32(MAX_FRAME_TIME),
-        );
-        
         // Draw the current frame
         if let Event::RedrawRequested(_) = event {
-            world.draw(pixels.get_frame_mut(), camera_offset);
+            let frame = pixels.get_frame_mut();
+            match (&game, &puzzles) {
+                (Some(game), _) => game.draw(frame, &font, timestep.alpha()),
+                (None, Some(list)) => draw_puzzle_menu(frame, &font, list, selected_puzzle),
+                (None, None) => draw_mode_menu(frame, &font, selected),
+            }
             if let Err(err) = pixels.render() {
                 error!("pixels.render() failed: {err}");
                 *control_flow = ControlFlow::Exit;
@@ -283,7 +946,8 @@ fn main() -> Result<(), Error> {
 
         // Handle input events
         if input.update(&event) {
-            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+            let in_menu = game.is_none() && puzzles.is_none();
+            if input.quit() || (input.key_pressed(VirtualKeyCode::Escape) && in_menu) {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
@@ -295,64 +959,282 @@ fn main() -> Result<(), Error> {
                     return;
                 }
             }
-            // Check if right arrow key is down
-            if input.key_pressed_os(VirtualKeyCode::Right) {
-                right_pressed = true;
-            }
 
-            // Check if right arrow key is up
-            if input.key_released(VirtualKeyCode::Right) {
-                right_pressed = false;
+            // Toggle the ghost piece
+            if input.key_pressed(VirtualKeyCode::G) {
+                settings.ghost = !settings.ghost;
+                if let Some(game) = &mut game {
+                    game.world.settings.ghost = settings.ghost;
+                }
             }
 
-            if right_pressed{
-                camera_offset.0 -=1;
-            }
+            let ticks = timestep.advance(Instant::now());
+            let mut leave_game = input.key_pressed(VirtualKeyCode::Escape);
+            if let Some(game) = &mut game {
+                // Latch presses so a tap shorter than a tick still registers
+                let mut held = Buttons::default();
+                for (key, button) in KEY_BINDINGS {
+                    if input.key_pressed(key) {
+                        input_handler.press(button);
+                    }
+                    if input.key_held(key) {
+                        held = held.with(button);
+                    }
+                }
 
-            // Check if left arrow key is down
-            if input.key_pressed_os(VirtualKeyCode::Left) {
-                left_pressed = true;
-            }
+                // Update internal state once for every tick that has passed
+                for _ in 0..ticks {
+                    game.update(&input_handler.tick(held));
+                }
 
-            // Check if left arrow key is up
-            if input.key_released(VirtualKeyCode::Left) {
-                left_pressed = false;
+                if game.status != Status::Playing && input.key_pressed(VirtualKeyCode::Return) {
+                    leave_game = true;
+                }
+            } else if let Some(list) = &puzzles {
+                // Pick a puzzle from the level directory
+                let count = list.len().max(1);
+                if input.key_pressed(VirtualKeyCode::Up) {
+                    selected_puzzle = (selected_puzzle + count - 1) % count;
+                }
+                if input.key_pressed(VirtualKeyCode::Down) {
+                    selected_puzzle = (selected_puzzle + 1) % count;
+                }
+                if input.key_pressed(VirtualKeyCode::Return) {
+                    if let Some((path, _)) = list.get(selected_puzzle) {
+                        settings.puzzle = Some(path.to_string_lossy().into_owned());
+                        game = Some(Game::new(ModeKind::Puzzle, tiles.clone(), settings.clone()));
+                        input_handler = InputHandler::new(settings.das, settings.arr);
+                        puzzles = None;
+                    }
+                } else if input.key_pressed(VirtualKeyCode::Escape) {
+                    puzzles = None;
+                }
+            } else {
+                // Pick a mode from the list
+                let count = ModeKind::ALL.len();
+                if input.key_pressed(VirtualKeyCode::Up) {
+                    selected = (selected + count - 1) % count;
+                }
+                if input.key_pressed(VirtualKeyCode::Down) {
+                    selected = (selected + 1) % count;
+                }
+                if input.key_pressed(VirtualKeyCode::Return) {
+                    let kind = ModeKind::ALL[selected];
+                    if kind == ModeKind::Puzzle {
+                        puzzles = Some(level::list_puzzles());
+                        selected_puzzle = 0;
+                    } else {
+                        game = Some(Game::new(kind, tiles.clone(), settings.clone()));
+                        input_handler = InputHandler::new(settings.das, settings.arr);
+                    }
+                }
             }
-
-            if left_pressed{
-                camera_offset.0 +=1;
+            if leave_game {
+                game = None;
             }
+            window.request_redraw();
 
-            // Check if up arrow key is down
-            if input.key_pressed_os(VirtualKeyCode::Up) {
-                up_pressed = true;
+            let new_title = match &game {
+                Some(game) => game.title(),
+                None => String::from("Bit World"),
+            };
+            if new_title != title {
+                window.set_title(&new_title);
+                title = new_title;
             }
 
-            // Check if up arrow key is up
-            if input.key_released(VirtualKeyCode::Up) {
-                up_pressed = false;
-            }
+            // Sleep until the next tick is due
+            *control_flow = ControlFlow::WaitUntil(timestep.next_tick());
+        }
+    });
+}
 
-            if up_pressed{
-                camera_offset.1 +=1;
-            }
+/// Tiles that draw nothing, so tests don't need the sprite sheet
+#[cfg(test)]
+fn test_tiles() -> Vec<Tile> {
+    (0..piece::TILE_COUNT).map(Tile::transparent).collect()
+}
 
-            // Check if down arrow key is down
-            if input.key_pressed_os(VirtualKeyCode::Down) {
-                down_pressed = true;
-            }
+/// A world on an empty board with `settings` that deals `pieces` in order, for tests
+#[cfg(test)]
+fn test_world(pieces: &[PieceKind], settings: Settings) -> World {
+    let mut world = World::new(test_tiles(), Board::new(), Settings { seed: Some(0), ..settings });
+    world.deal(pieces, None);
+    world
+}
 
-            // Check if down arrow key is up
-            if input.key_released(VirtualKeyCode::Down) {
-                down_pressed = false;
-            }
+/// A game run by `mode` with a seeded randomizer, for tests
+#[cfg(test)]
+fn test_game(mode: Box<dyn GameMode>, settings: Settings) -> Game {
+    Game::with_mode(mode, test_tiles(), Settings { seed: Some(0), ..settings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world_with(pieces: &[PieceKind]) -> World {
+        test_world(pieces, Settings::default())
+    }
+
+    /// Run `ticks` ticks without any input
+    fn idle(world: &mut World, ticks: u32) {
+        for _ in 0..ticks {
+            world.update(&[]);
+        }
+    }
+
+    #[test]
+    fn grounded_piece_locks_after_the_lock_delay() {
+        let mut world = world_with(&[PieceKind::O, PieceKind::T]);
+        world.update(&[Action::SonicDrop]);
+        idle(&mut world, LOCK_DELAY_FRAMES - 2);
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::O));
+        idle(&mut world, 1);
+        assert!(world.board.is_occupied(4, 39));
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::T));
+    }
+
+    #[test]
+    fn moving_on_the_ground_restarts_the_lock_delay() {
+        let mut world = world_with(&[PieceKind::O, PieceKind::T]);
+        world.update(&[Action::SonicDrop]);
+        idle(&mut world, 20);
+        world.update(&[Action::Shift(1)]);
+        assert_eq!(world.lock_resets, 1);
+        idle(&mut world, LOCK_DELAY_FRAMES - 2);
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::O));
+        idle(&mut world, 1);
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::T));
+    }
+
+    #[test]
+    fn lock_delay_resets_run_out() {
+        let mut world = world_with(&[PieceKind::O, PieceKind::T]);
+        world.update(&[Action::SonicDrop]);
+        for i in 0..MAX_LOCK_RESETS {
+            idle(&mut world, 5);
+            world.update(&[Action::Shift(if i % 2 == 0 { 1 } else { -1 })]);
+        }
+        assert_eq!(world.lock_resets, MAX_LOCK_RESETS);
+        assert_eq!(world.lock_timer, 1);
 
-            if down_pressed{
-                camera_offset.1 -=1;
+        // Still moves, but no longer restarts the timer
+        idle(&mut world, 5);
+        world.update(&[Action::Shift(1)]);
+        assert_eq!(world.lock_timer, 7);
+        idle(&mut world, LOCK_DELAY_FRAMES - 8);
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::O));
+        idle(&mut world, 1);
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::T));
+    }
+
+    #[test]
+    fn reaching_a_new_lowest_row_gives_the_resets_back() {
+        let mut world = world_with(&[PieceKind::O]);
+        // A ledge four rows high under the left six columns
+        for x in 0..6 {
+            for y in 36..40 {
+                world.board.set(x, y, GARBAGE_TILE_ID);
             }
         }
-        // Update internal state and request a redraw
-        world.update();
-        window.request_redraw();
-    });
+        world.gravity_override = Some(MAX_GRAVITY);
+        world.update(&[]);
+        let on_ledge = world.piece.unwrap().y;
+        world.update(&[Action::Shift(-1)]);
+        world.update(&[Action::Shift(1)]);
+        assert_eq!(world.lock_resets, 2);
+
+        // Step off the ledge and fall to the floor
+        world.update(&[Action::Shift(4)]);
+        assert_eq!(world.piece.unwrap().y, on_ledge + 4);
+        assert_eq!(world.lock_resets, 0);
+        assert_eq!(world.lock_timer, 1);
+    }
+
+    #[test]
+    fn hard_drop_locks_at_once_for_two_points_a_cell() {
+        let mut world = world_with(&[PieceKind::O, PieceKind::T]);
+        let piece = world.piece.unwrap();
+        let fall = world.landing_position(&piece).y - piece.y;
+        world.update(&[Action::HardDrop]);
+        assert!(world.board.is_occupied(4, 39));
+        assert!(world.board.is_occupied(5, 38));
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::T));
+        assert_eq!(world.scoring.score, 2 * fall as u64);
+    }
+
+    #[test]
+    fn sonic_drop_leaves_the_piece_free_to_move() {
+        let mut world = world_with(&[PieceKind::O, PieceKind::T]);
+        let piece = world.piece.unwrap();
+        let landed = world.landing_position(&piece);
+        world.update(&[Action::SonicDrop]);
+        assert_eq!(world.piece.map(|piece| (piece.x, piece.y)), Some((landed.x, landed.y)));
+        assert!(!world.board.is_occupied(4, 39));
+        assert_eq!(world.scoring.score, (landed.y - piece.y) as u64);
+
+        world.update(&[Action::Shift(-1)]);
+        assert_eq!(world.piece.unwrap().x, landed.x - 1);
+        idle(&mut world, LOCK_DELAY_FRAMES);
+        assert!(world.board.is_occupied(3, 39));
+        assert!(!world.board.is_occupied(5, 39));
+        assert_eq!(world.piece.map(|piece| piece.kind), Some(PieceKind::T));
+    }
+
+    #[test]
+    fn soft_drop_scores_a_point_a_row_fallen() {
+        let mut world = world_with(&[PieceKind::O]);
+        let start = world.piece.unwrap().y;
+        for _ in 0..30 {
+            world.update(&[Action::SoftDrop]);
+        }
+        let fallen = world.piece.unwrap().y - start;
+        // Twenty times level 1 gravity
+        assert_eq!(fallen as u32, 30 * gravity_for_level(1) * 20 / SUBCELLS_PER_CELL);
+        assert_eq!(world.scoring.score, fallen as u64);
+
+        // Plain gravity scores nothing
+        idle(&mut world, 120);
+        assert!(world.piece.unwrap().y > start + fallen);
+        assert_eq!(world.scoring.score, fallen as u64);
+    }
+
+    #[test]
+    fn tinted_tiles_share_the_sheet() {
+        let tiles = load_tiles();
+        let block = &tiles[GARBAGE_TILE_ID as usize].sprite;
+        for kind in PieceKind::ALL {
+            let sprite = &tiles[kind.tile_id() as usize].sprite;
+            assert!(Rc::ptr_eq(&sprite.image, &block.image), "{kind:?}");
+            assert_eq!(sprite.region, block.region);
+            assert_eq!(sprite.mode, DrawMode::Tint(kind.color()));
+        }
+    }
+
+    #[test]
+    fn falling_piece_is_drawn_partway_to_its_next_row() {
+        let mut world = world_with(&[PieceKind::O]);
+        world.gravity_override = Some(SUBCELLS_PER_CELL);
+        assert_eq!(world.fall_offset(0.0), 0);
+        assert_eq!(world.fall_offset(0.5), BLOCK_SIZE as i32 / 2);
+
+        // Not through the floor
+        world.update(&[Action::SonicDrop]);
+        assert_eq!(world.fall_offset(0.5), 0);
+    }
+
+    #[test]
+    fn gravity_is_20g_from_level_19() {
+        assert!(gravity_for_level(18) < MAX_GRAVITY);
+        assert_eq!(gravity_for_level(19), MAX_GRAVITY);
+        assert_eq!(gravity_for_level(30), MAX_GRAVITY);
+
+        let mut world = world_with(&[PieceKind::T]);
+        world.level = 19;
+        world.update(&[]);
+        assert!(world.is_grounded());
+        assert_eq!(world.piece.unwrap().y, 38);
+        assert_eq!(world.lock_timer, 1);
+    }
 }
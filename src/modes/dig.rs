@@ -0,0 +1,125 @@
+//! Dig: the well starts full of garbage and the race is to clear all of it.
+
+use super::sprint::format_time;
+use super::{GameMode, ModeKind, Status};
+use crate::board::EMPTY;
+use crate::garbage::garbage_map;
+use crate::level::{log_errors, Level};
+use crate::piece::{FIRST_PIECE_TILE_ID, TILE_COUNT};
+use crate::rng::Rng;
+use crate::World;
+use std::path::Path;
+
+/// Where the starting garbage comes from
+pub enum DigSource {
+    /// Generate this many rows with the given messiness, see `garbage_map`
+    Generated { rows: usize, messiness: u32, seed: u64 },
+    /// Load a hand-authored level file
+    Level(String),
+}
+
+pub struct Dig {
+    source: DigSource,
+    start_frame: u64,
+    finish_frame: Option<u64>,
+}
+
+impl Dig {
+    pub fn new(source: DigSource) -> Self {
+        Self {
+            source,
+            start_frame: 0,
+            finish_frame: None,
+        }
+    }
+
+    fn starting_level(&self) -> Level {
+        match &self.source {
+            DigSource::Generated { rows, messiness, seed } => {
+                Level::parse(&garbage_map(*rows, *messiness, &mut Rng::new(*seed)))
+                    .expect("generated garbage is always a valid level")
+            }
+            DigSource::Level(path) => {
+                let path = Path::new(path);
+                Level::load(path).unwrap_or_else(|errors| {
+                    log_errors(path, &errors);
+                    Level::empty()
+                })
+            }
+        }
+    }
+}
+
+/// Garbage is anything locked that wasn't placed by a piece
+fn is_garbage(id: u8) -> bool {
+    id != EMPTY && !(FIRST_PIECE_TILE_ID..TILE_COUNT).contains(&id)
+}
+
+impl GameMode for Dig {
+    fn name(&self) -> &'static str {
+        ModeKind::Dig.name()
+    }
+
+    fn start(&mut self, world: &mut World) {
+        let level = self.starting_level();
+        world.board = level.board;
+        world.define_tiles(&level.tiles);
+        world.respawn_piece();
+        self.start_frame = world.frame;
+        self.finish_frame = None;
+    }
+
+    fn update(&mut self, world: &mut World) -> Status {
+        if self.finish_frame.is_some() {
+            return Status::Cleared;
+        }
+        if world.game_over {
+            return Status::ToppedOut;
+        }
+        if world.board.count_rows(is_garbage) == 0 {
+            self.finish_frame = Some(world.frame);
+            return Status::Cleared;
+        }
+        Status::Playing
+    }
+
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)> {
+        let time = self.finish_frame.unwrap_or(world.frame) - self.start_frame;
+        vec![
+            ("Time", format_time(time)),
+            ("Garbage", world.board.count_rows(is_garbage).to_string()),
+            ("Lines", world.scoring.lines.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceKind;
+    use crate::settings::Settings;
+    use crate::test_world;
+
+    fn start(rows: usize) -> World {
+        let mut world = test_world(&[PieceKind::T, PieceKind::O], Settings::default());
+        let mut dig = Dig::new(DigSource::Generated { rows, messiness: 50, seed: 3 });
+        dig.start(&mut world);
+        world
+    }
+
+    #[test]
+    fn piece_spawns_above_the_garbage() {
+        let world = start(10);
+        assert_eq!(world.board.count_rows(is_garbage), 10);
+        let piece = world.piece.unwrap();
+        assert_eq!(piece.kind, PieceKind::T);
+        assert!(world.board.fits(piece.board_cells()));
+    }
+
+    #[test]
+    fn garbage_up_to_the_spawn_rows_tops_out() {
+        let world = start(21);
+        assert!(world.game_over);
+        assert!(world.piece.is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//! Marathon: clear lines to level up, with gravity getting faster every level.
+
+use super::{GameMode, ModeKind, Status};
+use crate::scoring::{base_points, ClearEvent};
+use crate::World;
+use std::str::FromStr;
+
+/// The level a non-endless marathon is finished after
+pub const FINAL_LEVEL: u32 = 15;
+
+/// How many lines it takes to reach the next level
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LevelGoal {
+    /// Every 10 lines cleared
+    Fixed,
+    /// 5 times the current level, counting the lines each clear is worth rather than the rows removed
+    Variable,
+}
+
+impl FromStr for LevelGoal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fixed" => Ok(LevelGoal::Fixed),
+            "variable" => Ok(LevelGoal::Variable),
+            _ => Err(format!("unknown level goal '{s}'")),
+        }
+    }
+}
+
+impl LevelGoal {
+    /// Lines needed to finish `level`
+    fn lines_for(self, level: u32) -> u32 {
+        match self {
+            LevelGoal::Fixed => 10,
+            LevelGoal::Variable => 5 * level,
+        }
+    }
+
+    /// How many lines `event` counts for towards the goal
+    fn lines_awarded(self, event: &ClearEvent) -> u32 {
+        match self {
+            LevelGoal::Fixed => event.lines,
+            LevelGoal::Variable => {
+                let awarded = (base_points(event.lines, event.spin) / 100) as u32;
+                if event.back_to_back {
+                    awarded * 3 / 2
+                } else {
+                    awarded
+                }
+            }
+        }
+    }
+}
+
+pub struct Marathon {
+    start_level: u32,
+    goal: LevelGoal,
+    /// Keep going past `FINAL_LEVEL`
+    endless: bool,
+    /// Lines counted towards the current level's goal
+    progress: u32,
+}
+
+impl Marathon {
+    pub fn new(start_level: u32, goal: LevelGoal, endless: bool) -> Self {
+        Self {
+            start_level: start_level.max(1),
+            goal,
+            endless,
+            progress: 0,
+        }
+    }
+
+    /// Lines still needed to reach the next level
+    fn lines_to_go(&self, level: u32) -> u32 {
+        self.goal.lines_for(level).saturating_sub(self.progress)
+    }
+}
+
+impl GameMode for Marathon {
+    fn name(&self) -> &'static str {
+        ModeKind::Marathon.name()
+    }
+
+    fn start(&mut self, world: &mut World) {
+        world.level = self.start_level;
+        self.progress = 0;
+    }
+
+    fn update(&mut self, world: &mut World) -> Status {
+        if world.game_over {
+            return Status::ToppedOut;
+        }
+
+        for event in &world.clear_events {
+            self.progress += self.goal.lines_awarded(event);
+        }
+        while self.progress >= self.goal.lines_for(world.level) {
+            self.progress -= self.goal.lines_for(world.level);
+            if world.level >= FINAL_LEVEL && !self.endless {
+                return Status::Cleared;
+            }
+            world.level += 1;
+        }
+        Status::Playing
+    }
+
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)> {
+        vec![
+            ("Level", world.level.to_string()),
+            ("Lines", world.scoring.lines.to_string()),
+            ("Goal", self.lines_to_go(world.level).to_string()),
+            ("Score", world.scoring.score.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceKind;
+    use crate::scoring::Spin;
+    use crate::settings::Settings;
+    use crate::test_world;
+
+    fn clear(lines: u32, back_to_back: bool) -> ClearEvent {
+        ClearEvent {
+            lines,
+            spin: Spin::None,
+            back_to_back,
+            combo: 0,
+            perfect_clear: false,
+            points: 0,
+        }
+    }
+
+    /// Start `marathon` and run one update per clear
+    fn play(marathon: &mut Marathon, clears: &[ClearEvent]) -> (World, Status) {
+        let mut world = test_world(&[PieceKind::I], Settings::default());
+        marathon.start(&mut world);
+        let mut status = Status::Playing;
+        for &event in clears {
+            world.clear_events = vec![event];
+            status = marathon.update(&mut world);
+        }
+        (world, status)
+    }
+
+    #[test]
+    fn fixed_goal_is_ten_lines_a_level() {
+        let mut marathon = Marathon::new(1, LevelGoal::Fixed, false);
+        let (world, _) = play(&mut marathon, &[clear(4, false), clear(4, true), clear(1, false)]);
+        assert_eq!((world.level, marathon.lines_to_go(world.level)), (1, 1));
+
+        let (world, _) = play(&mut marathon, &[clear(4, false), clear(4, true), clear(3, false)]);
+        assert_eq!((world.level, marathon.lines_to_go(world.level)), (2, 9));
+    }
+
+    #[test]
+    fn variable_goal_counts_awarded_lines() {
+        let mut marathon = Marathon::new(1, LevelGoal::Variable, false);
+        // A tetris is worth 8 of level 1's 5 lines, the rest carries over
+        let (world, _) = play(&mut marathon, &[clear(4, false)]);
+        assert_eq!((world.level, marathon.progress), (2, 3));
+
+        // Back to back it is worth 12, reaching level 2's 10
+        let (world, _) = play(&mut marathon, &[clear(4, false), clear(4, true)]);
+        assert_eq!((world.level, marathon.progress), (3, 5));
+
+        // A single is worth 1 and a double 3
+        let (world, _) = play(&mut marathon, &[clear(1, false), clear(2, false)]);
+        assert_eq!((world.level, marathon.progress), (1, 4));
+    }
+
+    #[test]
+    fn finishes_at_the_final_level_unless_endless() {
+        let mut marathon = Marathon::new(FINAL_LEVEL, LevelGoal::Fixed, false);
+        let (world, status) = play(&mut marathon, &[clear(4, false), clear(4, true), clear(2, false)]);
+        assert_eq!((world.level, status), (FINAL_LEVEL, Status::Cleared));
+
+        let mut marathon = Marathon::new(FINAL_LEVEL, LevelGoal::Fixed, true);
+        let (world, status) = play(&mut marathon, &[clear(4, false), clear(4, true), clear(2, false)]);
+        assert_eq!((world.level, status), (FINAL_LEVEL + 1, Status::Playing));
+    }
+}
@@ -0,0 +1,128 @@
+//! Game modes sit on top of the `World`: they set it up, watch its clear events and decide when the game ends.
+
+pub mod dig;
+pub mod marathon;
+pub mod puzzle;
+pub mod sprint;
+pub mod ultra;
+pub mod zen;
+
+use crate::level::{log_errors, Level};
+use crate::settings::{self, Settings};
+use crate::World;
+use dig::{Dig, DigSource};
+use marathon::Marathon;
+use puzzle::Puzzle;
+use sprint::Sprint;
+use ultra::Ultra;
+use zen::Zen;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Where a game stands after an update
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Playing,
+    /// The mode's goal was reached
+    Cleared,
+    /// The stack reached the top of the well
+    ToppedOut,
+    /// A fixed piece sequence ran out before the goal was reached
+    OutOfPieces,
+}
+
+pub trait GameMode {
+    fn name(&self) -> &'static str;
+
+    /// Prepare the world before the first piece moves
+    fn start(&mut self, world: &mut World);
+
+    /// React to the tick the world just simulated
+    fn update(&mut self, world: &mut World) -> Status;
+
+    /// Label and value pairs for the HUD
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)>;
+}
+
+/// The available modes, chosen in the settings
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModeKind {
+    Marathon,
+    Sprint,
+    Ultra,
+    Zen,
+    Dig,
+    Puzzle,
+}
+
+impl ModeKind {
+    /// Every mode, in the order they are listed in the mode select
+    pub const ALL: [ModeKind; 6] = [
+        ModeKind::Marathon,
+        ModeKind::Sprint,
+        ModeKind::Ultra,
+        ModeKind::Zen,
+        ModeKind::Dig,
+        ModeKind::Puzzle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ModeKind::Marathon => "Marathon",
+            ModeKind::Sprint => "Sprint",
+            ModeKind::Ultra => "Ultra",
+            ModeKind::Zen => "Zen",
+            ModeKind::Dig => "Dig",
+            ModeKind::Puzzle => "Puzzle",
+        }
+    }
+
+    /// Create a mode of this kind, configured from `settings`
+    pub fn build(self, settings: &Settings) -> Box<dyn GameMode> {
+        match self {
+            ModeKind::Marathon => Box::new(Marathon::new(
+                settings.start_level,
+                settings.level_goal,
+                settings.endless,
+            )),
+            ModeKind::Sprint => Box::new(Sprint::new(settings.sprint_lines)),
+            ModeKind::Ultra => Box::new(Ultra::new()),
+            ModeKind::Zen => Box::new(Zen::new()),
+            ModeKind::Dig => {
+                let source = match &settings.dig_level {
+                    Some(path) => DigSource::Level(path.clone()),
+                    None => DigSource::Generated {
+                        rows: settings.dig_rows,
+                        messiness: settings.dig_messiness,
+                        seed: settings.seed.unwrap_or_else(settings::clock_seed),
+                    },
+                };
+                Box::new(Dig::new(source))
+            }
+            ModeKind::Puzzle => {
+                let path = Path::new(settings.puzzle.as_deref().unwrap_or(puzzle::DEFAULT_PUZZLE));
+                let level = Level::load(path).unwrap_or_else(|errors| {
+                    log_errors(path, &errors);
+                    Level::empty()
+                });
+                Box::new(Puzzle::new(level))
+            }
+        }
+    }
+}
+
+impl FromStr for ModeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "marathon" => Ok(ModeKind::Marathon),
+            "sprint" => Ok(ModeKind::Sprint),
+            "ultra" => Ok(ModeKind::Ultra),
+            "zen" => Ok(ModeKind::Zen),
+            "dig" | "cheese" => Ok(ModeKind::Dig),
+            "puzzle" => Ok(ModeKind::Puzzle),
+            _ => Err(format!("unknown mode '{s}'")),
+        }
+    }
+}
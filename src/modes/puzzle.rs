@@ -0,0 +1,153 @@
+//! Puzzle: a hand-authored board, a fixed set of pieces and a goal to reach with them.
+
+use super::{GameMode, ModeKind, Status};
+use crate::level::{Goal, Level};
+use crate::scoring::Spin;
+use crate::World;
+
+/// Played when no puzzle has been picked
+pub const DEFAULT_PUZZLE: &str = "levels/level_00.data";
+
+pub struct Puzzle {
+    level: Level,
+    /// T-spins performed so far, with or without lines
+    tspins: u32,
+    perfect_clear: bool,
+}
+
+impl Puzzle {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            tspins: 0,
+            perfect_clear: false,
+        }
+    }
+
+    fn goal_reached(&self, world: &World) -> bool {
+        match self.level.goal {
+            Some(Goal::Lines(lines)) => world.scoring.lines >= lines,
+            Some(Goal::PerfectClear) => self.perfect_clear,
+            Some(Goal::TSpins(count)) => self.tspins >= count,
+            None => false,
+        }
+    }
+}
+
+impl GameMode for Puzzle {
+    fn name(&self) -> &'static str {
+        ModeKind::Puzzle.name()
+    }
+
+    fn start(&mut self, world: &mut World) {
+        world.board = self.level.board.clone();
+        world.define_tiles(&self.level.tiles);
+        world.deal(&self.level.pieces, self.level.holds);
+        self.tspins = 0;
+        self.perfect_clear = false;
+    }
+
+    fn update(&mut self, world: &mut World) -> Status {
+        for event in &world.clear_events {
+            if event.spin != Spin::None {
+                self.tspins += 1;
+            }
+            self.perfect_clear |= event.perfect_clear;
+        }
+
+        if self.goal_reached(world) {
+            Status::Cleared
+        } else if world.game_over {
+            Status::ToppedOut
+        } else if world.out_of_pieces() {
+            Status::OutOfPieces
+        } else {
+            Status::Playing
+        }
+    }
+
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)> {
+        let mut hud = vec![("Puzzle", self.level.name.clone())];
+        if let Some(goal) = self.level.goal {
+            hud.push(("Goal", goal.describe()));
+        }
+        let pieces_left = world.queue.len() + world.piece.is_some() as usize;
+        hud.push(("Pieces", pieces_left.to_string()));
+        if let Some(holds) = world.holds_left {
+            hud.push(("Holds", holds.to_string()));
+        }
+        hud
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+    use crate::piece::{PieceKind, Turn};
+    use crate::settings::Settings;
+    use crate::{test_game, Game};
+
+    /// A puzzle game on `rows`, with `#` for dirt, and the rest of the header given
+    fn puzzle(header: &str, rows: &str) -> Game {
+        let height = rows.lines().count();
+        let legend = "legend: . = empty\nlegend: # = dirt";
+        let text = format!("version: 1\nname: Test\nwidth: 10\nheight: {height}\n{legend}\n{header}\n---\n{rows}\n");
+        let level = Level::parse(&text).unwrap();
+        test_game(Box::new(Puzzle::new(level)), Settings::default())
+    }
+
+    /// Play one action a tick, returning the status after the last
+    fn play(game: &mut Game, actions: &[Action]) -> Status {
+        for &action in actions {
+            game.update(&[action]);
+        }
+        game.status
+    }
+
+    /// The flat I piece shifted over to the right wall
+    const I_RIGHT: [Action; 2] = [Action::Shift(3), Action::HardDrop];
+
+    #[test]
+    fn reaching_the_lines_goal_clears() {
+        let mut game = puzzle("pieces: I O\ngoal: lines 1", "######....");
+        assert_eq!(play(&mut game, &I_RIGHT), Status::Cleared);
+        assert_eq!(game.world.scoring.lines, 1);
+    }
+
+    #[test]
+    fn running_out_of_pieces_short_of_the_goal() {
+        let mut game = puzzle("pieces: I\ngoal: lines 2", "######....\n######....");
+        assert_eq!(play(&mut game, &[Action::Shift(3)]), Status::Playing);
+        assert_eq!(play(&mut game, &[Action::HardDrop]), Status::OutOfPieces);
+        assert_eq!(game.world.scoring.lines, 1);
+    }
+
+    #[test]
+    fn only_t_spins_count_for_a_t_spin_goal() {
+        let mut game = puzzle("pieces: I\ngoal: tspins 1", "######....");
+        assert_eq!(play(&mut game, &I_RIGHT), Status::OutOfPieces);
+        assert_eq!(game.world.scoring.lines, 1);
+
+        // A T-spin double: stand the T up, drop it beside the overhang and turn it into the slot
+        let mut game = puzzle("pieces: T\ngoal: tspins 1", "#.........\n...#######\n#.########");
+        let actions = [
+            Action::Rotate(Turn::Clockwise),
+            Action::Shift(-3),
+            Action::SonicDrop,
+            Action::Rotate(Turn::Clockwise),
+            Action::HardDrop,
+        ];
+        assert_eq!(play(&mut game, &actions), Status::Cleared);
+        assert_eq!(game.world.scoring.lines, 2);
+    }
+
+    #[test]
+    fn no_holds_means_hold_does_nothing() {
+        let mut game = puzzle("pieces: I O\nhold: 0\ngoal: lines 1", "######....");
+        assert_eq!(play(&mut game, &[Action::Hold]), Status::Playing);
+        assert_eq!(game.world.piece.map(|piece| piece.kind), Some(PieceKind::I));
+        assert_eq!(game.world.hold, None);
+        assert_eq!(game.world.queue.len(), 1);
+    }
+}
@@ -0,0 +1,262 @@
+//! Sprint: clear a set number of lines as fast as possible, racing a personal best.
+
+use super::{GameMode, ModeKind, Status};
+use crate::timestep::TICKS_PER_SECOND;
+use crate::World;
+use log::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Line targets a sprint can be played to
+pub const SPRINT_LENGTHS: [u32; 3] = [20, 40, 100];
+
+/// Lines between splits
+const SPLIT_LINES: u32 = 10;
+
+/// Personal bests are kept here, one file per sprint length
+const SAVE_DIR: &str = "saves";
+
+pub struct Sprint {
+    target_lines: u32,
+    /// World frame the sprint started on
+    start_frame: u64,
+    /// Ticks from the start at every split, the last one is the finishing time
+    splits: Vec<u64>,
+    /// Splits of the personal best run
+    best: Option<Vec<u64>>,
+    /// Where personal bests are read from and saved to, `None` keeps them for this run only
+    save_dir: Option<PathBuf>,
+    finished: bool,
+}
+
+impl Sprint {
+    pub fn new(target_lines: u32) -> Self {
+        Self::with_save_dir(target_lines, Some(Path::new(SAVE_DIR)))
+    }
+
+    /// A sprint that keeps its personal best in `save_dir`, or nowhere if `None`
+    pub fn with_save_dir(target_lines: u32, save_dir: Option<&Path>) -> Self {
+        let mut sprint = Self {
+            target_lines,
+            start_frame: 0,
+            splits: Vec::new(),
+            best: None,
+            save_dir: save_dir.map(Path::to_path_buf),
+            finished: false,
+        };
+        sprint.best = sprint.load_best();
+        sprint
+    }
+
+    fn best_path(&self) -> Option<PathBuf> {
+        Some(self.save_dir.as_ref()?.join(format!("sprint_{}.txt", self.target_lines)))
+    }
+
+    /// Read the personal best splits, one tick count per line
+    fn load_best(&self) -> Option<Vec<u64>> {
+        let contents = fs::read_to_string(self.best_path()?).ok()?;
+        let splits: Result<Vec<u64>, _> = contents.lines().map(|line| line.trim().parse()).collect();
+        splits.ok().filter(|splits| !splits.is_empty())
+    }
+
+    fn save_best(&self) {
+        let (Some(dir), Some(path)) = (&self.save_dir, self.best_path()) else {
+            return;
+        };
+        let contents: String = self.splits.iter().map(|split| format!("{split}\n")).collect();
+        let result = fs::create_dir_all(dir).and_then(|_| fs::write(path, contents));
+        if let Err(err) = result {
+            error!("Saving sprint personal best failed: {err}");
+        }
+    }
+
+    fn elapsed(&self, world: &World) -> u64 {
+        world.frame - self.start_frame
+    }
+
+    /// Difference to the personal best at the latest split, negative is ahead
+    fn split_delta(&self) -> Option<i64> {
+        let index = self.splits.len().checked_sub(1)?;
+        let best = self.best.as_ref()?.get(index)?;
+        Some(self.splits[index] as i64 - *best as i64)
+    }
+}
+
+impl GameMode for Sprint {
+    fn name(&self) -> &'static str {
+        ModeKind::Sprint.name()
+    }
+
+    fn start(&mut self, world: &mut World) {
+        self.start_frame = world.frame;
+        self.splits.clear();
+        self.finished = false;
+    }
+
+    fn update(&mut self, world: &mut World) -> Status {
+        if self.finished {
+            return Status::Cleared;
+        }
+        if world.game_over {
+            return Status::ToppedOut;
+        }
+
+        // One split for every 10 lines passed, a tetris can pass a split and finish in the same clear
+        let lines = world.scoring.lines.min(self.target_lines);
+        let elapsed = self.elapsed(world);
+        while (self.splits.len() as u32 + 1) * SPLIT_LINES <= lines {
+            self.splits.push(elapsed);
+        }
+
+        if lines < self.target_lines {
+            return Status::Playing;
+        }
+
+        // The finishing time is the last split, unless it already landed on one
+        if !self.target_lines.is_multiple_of(SPLIT_LINES) {
+            self.splits.push(elapsed);
+        }
+        self.finished = true;
+
+        let best_time = self.best.as_ref().and_then(|best| best.last().copied());
+        if best_time.is_none_or(|best| elapsed < best) {
+            info!("New {} line sprint personal best: {}", self.target_lines, format_time(elapsed));
+            self.save_best();
+            self.best = Some(self.splits.clone());
+        }
+        Status::Cleared
+    }
+
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)> {
+        let time = match (self.finished, self.splits.last()) {
+            (true, Some(&finish)) => finish,
+            _ => self.elapsed(world),
+        };
+        let mut hud = vec![
+            ("Time", format_time(time)),
+            ("Lines", format!("{}/{}", world.scoring.lines.min(self.target_lines), self.target_lines)),
+        ];
+        if let Some(delta) = self.split_delta() {
+            let sign = if delta < 0 { '-' } else { '+' };
+            hud.push(("Split", format!("{sign}{}", format_time(delta.unsigned_abs()))));
+        }
+        if let Some(best) = self.best.as_ref().and_then(|best| best.last()) {
+            hud.push(("Best", format_time(*best)));
+        }
+        hud
+    }
+}
+
+/// Format a tick count as minutes, seconds and milliseconds
+pub fn format_time(ticks: u64) -> String {
+    let millis = ticks * 1000 / TICKS_PER_SECOND as u64;
+    format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceKind;
+    use crate::settings::Settings;
+    use crate::test_world;
+    use std::env;
+
+    fn start(target_lines: u32, best: Option<Vec<u64>>) -> (Sprint, World) {
+        let mut world = test_world(&[PieceKind::I], Settings::default());
+        let mut sprint = Sprint::with_save_dir(target_lines, None);
+        sprint.best = best;
+        sprint.start(&mut world);
+        (sprint, world)
+    }
+
+    /// Advance to `ticks` after the start with `lines` cleared and update the sprint
+    fn reach(sprint: &mut Sprint, world: &mut World, ticks: u64, lines: u32) -> Status {
+        world.frame = sprint.start_frame + ticks;
+        world.scoring.lines = lines;
+        sprint.update(world)
+    }
+
+    #[test]
+    fn a_split_every_ten_lines() {
+        let (mut sprint, mut world) = start(40, None);
+        assert_eq!(reach(&mut sprint, &mut world, 100, 9), Status::Playing);
+        assert!(sprint.splits.is_empty());
+        reach(&mut sprint, &mut world, 120, 10);
+        // A tetris from 18 to 22 passes the second split
+        reach(&mut sprint, &mut world, 200, 18);
+        reach(&mut sprint, &mut world, 250, 22);
+        assert_eq!(sprint.splits, [120, 250]);
+    }
+
+    #[test]
+    fn one_clear_can_pass_a_split_and_finish() {
+        let (mut sprint, mut world) = start(40, None);
+        reach(&mut sprint, &mut world, 100, 28);
+        assert_eq!(reach(&mut sprint, &mut world, 400, 32), Status::Playing);
+        // A tetris from 38 takes the last split and finishes at the same time
+        reach(&mut sprint, &mut world, 500, 38);
+        assert_eq!(reach(&mut sprint, &mut world, 600, 42), Status::Cleared);
+        assert_eq!(sprint.splits, [100, 100, 400, 600]);
+        assert_eq!(sprint.hud(&world)[1].1, "40/40");
+
+        // Off a split, the finish is a split of its own
+        let (mut sprint, mut world) = start(25, None);
+        reach(&mut sprint, &mut world, 300, 18);
+        assert_eq!(reach(&mut sprint, &mut world, 360, 22), Status::Playing);
+        assert_eq!(reach(&mut sprint, &mut world, 420, 26), Status::Cleared);
+        assert_eq!(sprint.splits, [300, 360, 420]);
+    }
+
+    #[test]
+    fn splits_compare_with_the_personal_best() {
+        let (mut sprint, mut world) = start(20, Some(vec![600, 1200]));
+        reach(&mut sprint, &mut world, 540, 10);
+        assert_eq!(sprint.split_delta(), Some(-60));
+        assert!(sprint.hud(&world).contains(&("Split", "-0:01.000".to_string())));
+
+        // Finishing slower keeps the old best
+        reach(&mut sprint, &mut world, 1260, 20);
+        assert_eq!(sprint.split_delta(), Some(60));
+        assert_eq!(sprint.best, Some(vec![600, 1200]));
+        assert!(sprint.hud(&world).contains(&("Best", "0:20.000".to_string())));
+    }
+
+    #[test]
+    fn a_faster_finish_is_the_new_best() {
+        let (mut sprint, mut world) = start(20, Some(vec![600, 1200]));
+        reach(&mut sprint, &mut world, 500, 10);
+        assert_eq!(reach(&mut sprint, &mut world, 1100, 20), Status::Cleared);
+        assert_eq!(sprint.best, Some(vec![500, 1100]));
+
+        // With no best yet, any finish is one
+        let (mut sprint, mut world) = start(20, None);
+        reach(&mut sprint, &mut world, 2000, 20);
+        assert_eq!(sprint.best, Some(vec![2000, 2000]));
+    }
+
+    #[test]
+    fn personal_bests_are_saved_and_loaded() {
+        // A directory that doesn't exist yet, inside a fresh one
+        let root = env::temp_dir().join(format!("bit_game_sprint_{}", std::process::id()));
+        let dir = root.join("saves");
+        let _ = fs::remove_dir_all(&root);
+        assert_eq!(Sprint::with_save_dir(20, Some(&dir)).best, None);
+
+        let mut world = test_world(&[PieceKind::I], Settings::default());
+        let mut sprint = Sprint::with_save_dir(20, Some(&dir));
+        sprint.start(&mut world);
+        reach(&mut sprint, &mut world, 500, 10);
+        reach(&mut sprint, &mut world, 1100, 20);
+        assert_eq!(fs::read_to_string(dir.join("sprint_20.txt")).unwrap(), "500\n1100\n");
+        assert_eq!(Sprint::with_save_dir(20, Some(&dir)).best, Some(vec![500, 1100]));
+        // Each length keeps its own
+        assert_eq!(Sprint::with_save_dir(40, Some(&dir)).best, None);
+
+        // A damaged file is no personal best rather than an error
+        for contents in ["", "500\nsoon\n", "-3\n"] {
+            fs::write(dir.join("sprint_20.txt"), contents).unwrap();
+            assert_eq!(Sprint::with_save_dir(20, Some(&dir)).best, None, "{contents:?}");
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+//! Ultra: score as many points as possible before the clock runs out.
+
+use super::sprint::format_time;
+use super::{GameMode, ModeKind, Status};
+use crate::timestep::TICKS_PER_SECOND;
+use crate::World;
+
+/// Length of an ultra game, two minutes
+pub const ULTRA_TICKS: u64 = 120 * TICKS_PER_SECOND as u64;
+
+pub struct Ultra {
+    /// World frame the game started on
+    start_frame: u64,
+}
+
+impl Ultra {
+    pub fn new() -> Self {
+        Self { start_frame: 0 }
+    }
+
+    fn remaining(&self, world: &World) -> u64 {
+        ULTRA_TICKS.saturating_sub(world.frame - self.start_frame)
+    }
+}
+
+impl GameMode for Ultra {
+    fn name(&self) -> &'static str {
+        ModeKind::Ultra.name()
+    }
+
+    fn start(&mut self, world: &mut World) {
+        self.start_frame = world.frame;
+    }
+
+    fn update(&mut self, world: &mut World) -> Status {
+        if world.game_over {
+            Status::ToppedOut
+        } else if self.remaining(world) == 0 {
+            Status::Cleared
+        } else {
+            Status::Playing
+        }
+    }
+
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)> {
+        vec![
+            ("Time", format_time(self.remaining(world))),
+            ("Lines", world.scoring.lines.to_string()),
+            ("Score", world.scoring.score.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use crate::test_game;
+
+    #[test]
+    fn clears_when_the_clock_runs_out() {
+        let mut game = test_game(Box::new(Ultra::new()), Settings::default());
+        let time = |game: &crate::Game| game.mode.hud(&game.world)[0].1.clone();
+        assert_eq!(time(&game), "2:00.000");
+
+        for _ in 0..TICKS_PER_SECOND {
+            game.update(&[]);
+        }
+        assert_eq!(time(&game), "1:59.000");
+
+        for _ in TICKS_PER_SECOND as u64..ULTRA_TICKS - 1 {
+            game.update(&[]);
+        }
+        assert_eq!(game.status, Status::Playing);
+        assert_eq!(time(&game), format_time(1));
+        game.update(&[]);
+        assert_eq!(game.status, Status::Cleared);
+        assert_eq!(time(&game), "0:00.000");
+    }
+}
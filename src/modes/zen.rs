@@ -0,0 +1,82 @@
+//! Zen: no gravity and no game over, the top of the stack is cleared away when it overflows.
+
+use super::{GameMode, ModeKind, Status};
+use crate::board::HIDDEN_ROWS;
+use crate::World;
+
+/// Visible rows cleared from the top when the stack overflows
+const OVERFLOW_CLEAR_ROWS: usize = 10;
+
+pub struct Zen;
+
+impl Zen {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl GameMode for Zen {
+    fn name(&self) -> &'static str {
+        ModeKind::Zen.name()
+    }
+
+    fn start(&mut self, world: &mut World) {
+        world.gravity_override = Some(0);
+    }
+
+    fn update(&mut self, world: &mut World) -> Status {
+        if world.game_over {
+            world.board.clear_rows(0..HIDDEN_ROWS + OVERFLOW_CLEAR_ROWS);
+            world.game_over = false;
+            world.spawn_piece();
+        }
+        Status::Playing
+    }
+
+    fn hud(&self, world: &World) -> Vec<(&'static str, String)> {
+        vec![
+            ("Lines", world.scoring.lines.to_string()),
+            ("Score", world.scoring.score.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::EMPTY;
+    use crate::input::Action;
+    use crate::settings::Settings;
+    use crate::test_game;
+
+    #[test]
+    fn pieces_only_fall_when_dropped() {
+        let mut game = test_game(Box::new(Zen::new()), Settings::default());
+        let spawned = game.world.piece.unwrap();
+        for _ in 0..600 {
+            game.update(&[]);
+        }
+        let piece = game.world.piece.unwrap();
+        assert_eq!((piece.kind, piece.y), (spawned.kind, spawned.y));
+    }
+
+    #[test]
+    fn overflowing_the_well_keeps_playing() {
+        let mut game = test_game(Box::new(Zen::new()), Settings::default());
+        let stack_height = |game: &crate::Game| game.world.board.count_rows(|id| id != EMPTY);
+        let mut overflows = 0;
+        for i in 0..400 {
+            let height = stack_height(&game);
+            let action = if i % 3 == 2 { Action::Hold } else { Action::HardDrop };
+            game.update(&[action]);
+            // More rows went than a line clear can take
+            if stack_height(&game) + 4 < height {
+                overflows += 1;
+            }
+            assert_eq!(game.status, Status::Playing, "tick {i}");
+            assert!(game.world.piece.is_some(), "tick {i}");
+            assert!(!game.world.game_over, "tick {i}");
+        }
+        assert!(overflows > 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! The seven tetrominoes and the falling piece the player is controlling.
+
+use crate::board::HIDDEN_ROWS;
+
+/// Column of the left edge of a piece's bounding box when it spawns
+const SPAWN_X: i32 = 3;
+/// Row of the top edge of a piece's bounding box when it spawns, so it sits in the two rows above the visible well
+const SPAWN_Y: i32 = HIDDEN_ROWS as i32 - 2;
+
+/// First tile id used by pieces, ids below this belong to the level tiles
+pub const FIRST_PIECE_TILE_ID: u8 = 3;
+/// Number of tile ids in use, one past the last piece's
+pub const TILE_COUNT: u8 = FIRST_PIECE_TILE_ID + PieceKind::ALL.len() as u8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+/// Cell offsets from the top left of the bounding box for every rotation state, in `Rotation` order.
+/// Rows grow downwards, the same as the board.
+const I_CELLS: [[(i32, i32); 4]; 4] = [
+    [(0, 1), (1, 1), (2, 1), (3, 1)],
+    [(2, 0), (2, 1), (2, 2), (2, 3)],
+    [(0, 2), (1, 2), (2, 2), (3, 2)],
+    [(1, 0), (1, 1), (1, 2), (1, 3)],
+];
+const O_CELLS: [[(i32, i32); 4]; 4] = [[(1, 0), (2, 0), (1, 1), (2, 1)]; 4];
+const T_CELLS: [[(i32, i32); 4]; 4] = [
+    [(1, 0), (0, 1), (1, 1), (2, 1)],
+    [(1, 0), (1, 1), (2, 1), (1, 2)],
+    [(0, 1), (1, 1), (2, 1), (1, 2)],
+    [(1, 0), (0, 1), (1, 1), (1, 2)],
+];
+const S_CELLS: [[(i32, i32); 4]; 4] = [
+    [(1, 0), (2, 0), (0, 1), (1, 1)],
+    [(1, 0), (1, 1), (2, 1), (2, 2)],
+    [(1, 1), (2, 1), (0, 2), (1, 2)],
+    [(0, 0), (0, 1), (1, 1), (1, 2)],
+];
+const Z_CELLS: [[(i32, i32); 4]; 4] = [
+    [(0, 0), (1, 0), (1, 1), (2, 1)],
+    [(2, 0), (1, 1), (2, 1), (1, 2)],
+    [(0, 1), (1, 1), (1, 2), (2, 2)],
+    [(1, 0), (0, 1), (1, 1), (0, 2)],
+];
+const J_CELLS: [[(i32, i32); 4]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (2, 1)],
+    [(1, 0), (2, 0), (1, 1), (1, 2)],
+    [(0, 1), (1, 1), (2, 1), (2, 2)],
+    [(1, 0), (1, 1), (0, 2), (1, 2)],
+];
+const L_CELLS: [[(i32, i32); 4]; 4] = [
+    [(2, 0), (0, 1), (1, 1), (2, 1)],
+    [(1, 0), (1, 1), (1, 2), (2, 2)],
+    [(0, 1), (1, 1), (2, 1), (0, 2)],
+    [(0, 0), (1, 0), (1, 1), (1, 2)],
+];
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::I,
+        PieceKind::O,
+        PieceKind::T,
+        PieceKind::S,
+        PieceKind::Z,
+        PieceKind::J,
+        PieceKind::L,
+    ];
+
+    /// The piece named by its letter, in either case
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            'I' => Some(PieceKind::I),
+            'O' => Some(PieceKind::O),
+            'T' => Some(PieceKind::T),
+            'S' => Some(PieceKind::S),
+            'Z' => Some(PieceKind::Z),
+            'J' => Some(PieceKind::J),
+            'L' => Some(PieceKind::L),
+            _ => None,
+        }
+    }
+
+    /// Cell offsets of the piece in the given rotation state
+    pub fn cells(self, rotation: Rotation) -> [(i32, i32); 4] {
+        let table = match self {
+            PieceKind::I => &I_CELLS,
+            PieceKind::O => &O_CELLS,
+            PieceKind::T => &T_CELLS,
+            PieceKind::S => &S_CELLS,
+            PieceKind::Z => &Z_CELLS,
+            PieceKind::J => &J_CELLS,
+            PieceKind::L => &L_CELLS,
+        };
+        table[rotation.index()]
+    }
+
+    /// Id of the tile this piece is drawn and locked with
+    pub fn tile_id(self) -> u8 {
+        FIRST_PIECE_TILE_ID + self as u8
+    }
+
+    /// Guideline colour of the piece
+    pub fn color(self) -> [u8; 4] {
+        match self {
+            PieceKind::I => [0, 240, 240, 255],
+            PieceKind::O => [240, 240, 0, 255],
+            PieceKind::T => [160, 0, 240, 255],
+            PieceKind::S => [0, 240, 0, 255],
+            PieceKind::Z => [240, 0, 0, 255],
+            PieceKind::J => [0, 0, 240, 255],
+            PieceKind::L => [240, 160, 0, 255],
+        }
+    }
+}
+
+/// The four rotation states, named after the guideline's 0, R, 2 and L
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Rotation {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn clockwise(self) -> Self {
+        match self {
+            Rotation::Spawn => Rotation::Right,
+            Rotation::Right => Rotation::Reverse,
+            Rotation::Reverse => Rotation::Left,
+            Rotation::Left => Rotation::Spawn,
+        }
+    }
+
+    pub fn counter_clockwise(self) -> Self {
+        match self {
+            Rotation::Spawn => Rotation::Left,
+            Rotation::Right => Rotation::Spawn,
+            Rotation::Reverse => Rotation::Right,
+            Rotation::Left => Rotation::Reverse,
+        }
+    }
+
+    /// The state after applying `turn` to this one
+    pub fn turned(self, turn: Turn) -> Self {
+        match turn {
+            Turn::Clockwise => self.clockwise(),
+            Turn::CounterClockwise => self.counter_clockwise(),
+            Turn::Half => self.clockwise().clockwise(),
+        }
+    }
+}
+
+/// A rotation input from the player
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Turn {
+    Clockwise,
+    CounterClockwise,
+    Half,
+}
+
+#[derive(Copy, Clone)]
+pub struct ActivePiece {
+    pub kind: PieceKind,
+    pub rotation: Rotation,
+    /// Column of the left edge of the piece's bounding box
+    pub x: i32,
+    /// Row of the top edge of the piece's bounding box
+    pub y: i32,
+}
+
+impl ActivePiece {
+    /// Create a new piece at the spawn position, just above the visible well
+    pub fn spawn(kind: PieceKind) -> Self {
+        Self {
+            kind,
+            rotation: Rotation::Spawn,
+            x: SPAWN_X,
+            y: SPAWN_Y,
+        }
+    }
+
+    /// Board coordinates of every cell in the piece
+    pub fn board_cells(&self) -> impl Iterator<Item = (i32, i32)> {
+        let (x, y) = (self.x, self.y);
+        self.kind
+            .cells(self.rotation)
+            .into_iter()
+            .map(move |(dx, dy)| (x + dx, y + dy))
+    }
+
+    pub fn tile_id(&self) -> u8 {
+        self.kind.tile_id()
+    }
+
+    /// A copy of this piece moved by `(dx, dy)`
+    pub fn shifted(&self, dx: i32, dy: i32) -> Self {
+        Self {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
+        }
+    }
+
+    /// A copy of this piece turned to `rotation` in place
+    pub fn rotated(&self, rotation: Rotation) -> Self {
+        Self { rotation, ..*self }
+    }
+}
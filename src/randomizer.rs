@@ -0,0 +1,224 @@
+//! Piece generators. Every generator is seeded so the same seed always deals the same pieces.
+
+use crate::piece::PieceKind;
+use crate::rng::Rng;
+use std::str::FromStr;
+
+pub trait Randomizer {
+    /// The next piece to deal
+    fn next(&mut self) -> PieceKind;
+}
+
+/// The available generators, chosen in the settings
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RandomizerKind {
+    Bag,
+    Random,
+    Tgm,
+    Nes,
+}
+
+impl RandomizerKind {
+    /// Create a generator of this kind seeded with `seed`
+    pub fn build(self, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::Bag => Box::new(SevenBag::new(seed)),
+            RandomizerKind::Random => Box::new(PureRandom::new(seed)),
+            RandomizerKind::Tgm => Box::new(TgmHistory::new(seed)),
+            RandomizerKind::Nes => Box::new(Nes::new(seed)),
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bag" | "7bag" => Ok(RandomizerKind::Bag),
+            "random" => Ok(RandomizerKind::Random),
+            "tgm" => Ok(RandomizerKind::Tgm),
+            "nes" => Ok(RandomizerKind::Nes),
+            _ => Err(format!("unknown randomizer '{s}'")),
+        }
+    }
+}
+
+/// Deals all seven pieces in a shuffled order before starting a new bag
+pub struct SevenBag {
+    rng: Rng,
+    bag: Vec<PieceKind>,
+}
+
+impl SevenBag {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            bag: Vec::with_capacity(PieceKind::ALL.len()),
+        }
+    }
+}
+
+impl Randomizer for SevenBag {
+    fn next(&mut self) -> PieceKind {
+        if self.bag.is_empty() {
+            self.bag.extend(PieceKind::ALL);
+            self.rng.shuffle(&mut self.bag);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// Every piece is equally likely every time, no matter what came before
+pub struct PureRandom {
+    rng: Rng,
+}
+
+impl PureRandom {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Rng::new(seed) }
+    }
+}
+
+impl Randomizer for PureRandom {
+    fn next(&mut self) -> PieceKind {
+        PieceKind::ALL[self.rng.below(PieceKind::ALL.len())]
+    }
+}
+
+/// Number of times the TGM generator rolls again when it hits a piece in its history
+const TGM_ROLLS: usize = 6;
+
+/// The Tetris The Grand Master generator: it remembers the last four pieces and rerolls
+/// a few times to avoid dealing one of them again
+pub struct TgmHistory {
+    rng: Rng,
+    history: [PieceKind; 4],
+    first: bool,
+}
+
+impl TgmHistory {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            history: [PieceKind::Z, PieceKind::Z, PieceKind::S, PieceKind::S],
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for TgmHistory {
+    fn next(&mut self) -> PieceKind {
+        let piece = if self.first {
+            // The first piece is never one that would leave an overhang
+            self.first = false;
+            let starters = [PieceKind::I, PieceKind::J, PieceKind::L, PieceKind::T];
+            starters[self.rng.below(starters.len())]
+        } else {
+            let mut piece = PieceKind::ALL[self.rng.below(PieceKind::ALL.len())];
+            for _ in 1..TGM_ROLLS {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = PieceKind::ALL[self.rng.below(PieceKind::ALL.len())];
+            }
+            piece
+        };
+
+        self.history.rotate_right(1);
+        self.history[0] = piece;
+        piece
+    }
+}
+
+/// The NES generator: rolls once more if it repeats the previous piece
+pub struct Nes {
+    rng: Rng,
+    previous: Option<PieceKind>,
+}
+
+impl Nes {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            previous: None,
+        }
+    }
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self) -> PieceKind {
+        // The first roll has an eighth "dummy" outcome that also forces a reroll
+        let roll = self.rng.below(PieceKind::ALL.len() + 1);
+        let piece = match PieceKind::ALL.get(roll) {
+            Some(&piece) if Some(piece) != self.previous => piece,
+            _ => PieceKind::ALL[self.rng.below(PieceKind::ALL.len())],
+        };
+        self.previous = Some(piece);
+        piece
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [RandomizerKind; 4] = [
+        RandomizerKind::Bag,
+        RandomizerKind::Random,
+        RandomizerKind::Tgm,
+        RandomizerKind::Nes,
+    ];
+
+    fn deal(randomizer: &mut dyn Randomizer, count: usize) -> Vec<PieceKind> {
+        (0..count).map(|_| randomizer.next()).collect()
+    }
+
+    #[test]
+    fn every_bag_holds_each_piece_once() {
+        for seed in 0..50 {
+            let pieces = deal(&mut SevenBag::new(seed), 7 * 20);
+            for bag in pieces.chunks(7) {
+                for kind in PieceKind::ALL {
+                    assert_eq!(bag.iter().filter(|&&piece| piece == kind).count(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_sequence() {
+        for kind in KINDS {
+            let first = deal(kind.build(1234).as_mut(), 200);
+            let second = deal(kind.build(1234).as_mut(), 200);
+            assert_eq!(first, second, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn different_seeds_deal_different_sequences() {
+        for kind in KINDS {
+            let first = deal(kind.build(1).as_mut(), 200);
+            let second = deal(kind.build(2).as_mut(), 200);
+            assert_ne!(first, second, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_z_or_o() {
+        for seed in 0..200 {
+            let first = TgmHistory::new(seed).next();
+            assert!(![PieceKind::S, PieceKind::Z, PieceKind::O].contains(&first));
+        }
+    }
+
+    #[test]
+    fn every_generator_deals_every_piece() {
+        for kind in KINDS {
+            let pieces = deal(kind.build(99).as_mut(), 500);
+            for piece in PieceKind::ALL {
+                assert!(pieces.contains(&piece), "{kind:?} never dealt {piece:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Guideline scoring: line clears, T-spins, combos, back-to-back and perfect clears.
+
+/// How a piece was dropped, each is worth a different amount per cell
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DropKind {
+    /// Soft and sonic drops
+    Soft,
+    Hard,
+}
+
+/// Whether the piece that locked was spun into place
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What happened when a piece locked, sent out for every line clear and every T-spin
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClearEvent {
+    pub lines: u32,
+    pub spin: Spin,
+    /// This clear continued a back-to-back chain and got the bonus
+    pub back_to_back: bool,
+    /// Consecutive line clears before this one, 0 for the first clear of a chain
+    pub combo: u32,
+    pub perfect_clear: bool,
+    /// Points awarded for this clear, bonuses included
+    pub points: u64,
+}
+
+impl ClearEvent {
+    /// Tetrises and T-spins that clear lines keep a back-to-back chain going
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.spin != Spin::None && self.lines > 0)
+    }
+}
+
+pub struct Scoring {
+    pub score: u64,
+    pub lines: u32,
+    /// Consecutive line clears so far, `None` once a piece locks without clearing
+    combo: Option<u32>,
+    /// The last line clear was a difficult one
+    back_to_back: bool,
+}
+
+impl Scoring {
+    pub fn new() -> Self {
+        Self {
+            score: 0,
+            lines: 0,
+            combo: None,
+            back_to_back: false,
+        }
+    }
+
+    /// Award points for a piece dropped `cells` rows by the player
+    pub fn drop(&mut self, kind: DropKind, cells: u32) {
+        let per_cell = match kind {
+            DropKind::Soft => 1,
+            DropKind::Hard => 2,
+        };
+        self.score += per_cell * cells as u64;
+    }
+
+    /// Score a piece locking at `level`. Returns the clear event, or `None` if the lock was worth nothing.
+    pub fn lock(&mut self, lines: u32, spin: Spin, perfect_clear: bool, level: u32) -> Option<ClearEvent> {
+        let level = level.max(1) as u64;
+
+        if lines == 0 {
+            self.combo = None;
+            if spin == Spin::None {
+                return None;
+            }
+        } else {
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+        }
+
+        let mut event = ClearEvent {
+            lines,
+            spin,
+            back_to_back: false,
+            combo: self.combo.unwrap_or(0),
+            perfect_clear,
+            points: 0,
+        };
+
+        let mut points = base_points(lines, spin) * level;
+        if lines > 0 {
+            // Only line clears build or break a back-to-back chain
+            let difficult = event.is_difficult();
+            event.back_to_back = difficult && self.back_to_back;
+            self.back_to_back = difficult;
+            if event.back_to_back {
+                points = points * 3 / 2;
+            }
+        }
+        points += 50 * event.combo as u64 * level;
+        if perfect_clear {
+            points += perfect_clear_points(lines, event.back_to_back) * level;
+        }
+
+        event.points = points;
+        self.score += points;
+        self.lines += lines;
+        Some(event)
+    }
+}
+
+/// Points for a clear at level 1, before any bonus
+pub fn base_points(lines: u32, spin: Spin) -> u64 {
+    match (spin, lines) {
+        (Spin::None, 1) => 100,
+        (Spin::None, 2) => 300,
+        (Spin::None, 3) => 500,
+        (Spin::None, 4) => 800,
+        (Spin::Mini, 0) => 100,
+        (Spin::Mini, 1) => 200,
+        (Spin::Mini, 2) => 400,
+        (Spin::Full, 0) => 400,
+        (Spin::Full, 1) => 800,
+        (Spin::Full, 2) => 1200,
+        (Spin::Full, 3) => 1600,
+        _ => 0,
+    }
+}
+
+/// Bonus for leaving the board empty at level 1
+fn perfect_clear_points(lines: u32, back_to_back: bool) -> u64 {
+    match lines {
+        1 => 800,
+        2 => 1200,
+        3 => 1800,
+        4 if back_to_back => 3200,
+        4 => 2000,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(scoring: &mut Scoring, lines: u32, spin: Spin, level: u32) -> u64 {
+        scoring.lock(lines, spin, false, level).map_or(0, |event| event.points)
+    }
+
+    /// Score one clear on its own, with no chain going
+    fn single_clear(lines: u32, spin: Spin) -> u64 {
+        points(&mut Scoring::new(), lines, spin, 1)
+    }
+
+    #[test]
+    fn line_clears_match_the_guideline() {
+        assert_eq!(single_clear(1, Spin::None), 100);
+        assert_eq!(single_clear(2, Spin::None), 300);
+        assert_eq!(single_clear(3, Spin::None), 500);
+        assert_eq!(single_clear(4, Spin::None), 800);
+    }
+
+    #[test]
+    fn t_spins_match_the_guideline() {
+        assert_eq!(single_clear(0, Spin::Mini), 100);
+        assert_eq!(single_clear(1, Spin::Mini), 200);
+        assert_eq!(single_clear(2, Spin::Mini), 400);
+        assert_eq!(single_clear(0, Spin::Full), 400);
+        assert_eq!(single_clear(1, Spin::Full), 800);
+        assert_eq!(single_clear(2, Spin::Full), 1200);
+        assert_eq!(single_clear(3, Spin::Full), 1600);
+    }
+
+    #[test]
+    fn points_scale_with_level() {
+        let mut scoring = Scoring::new();
+        assert_eq!(points(&mut scoring, 4, Spin::None, 5), 4000);
+        assert_eq!(scoring.score, 4000);
+        assert_eq!(scoring.lines, 4);
+    }
+
+    #[test]
+    fn back_to_back_is_worth_half_again() {
+        let mut scoring = Scoring::new();
+        assert_eq!(points(&mut scoring, 4, Spin::None, 1), 800);
+        scoring.lock(0, Spin::None, false, 1);
+        let event = scoring.lock(4, Spin::None, false, 1).unwrap();
+        assert!(event.back_to_back);
+        assert_eq!(event.points, 1200);
+
+        scoring.lock(0, Spin::None, false, 1);
+        assert_eq!(points(&mut scoring, 3, Spin::Full, 1), 2400);
+    }
+
+    #[test]
+    fn easy_clear_breaks_back_to_back() {
+        let mut scoring = Scoring::new();
+        scoring.lock(4, Spin::None, false, 1);
+        scoring.lock(0, Spin::None, false, 1);
+        scoring.lock(1, Spin::None, false, 1);
+        scoring.lock(0, Spin::None, false, 1);
+        let event = scoring.lock(4, Spin::None, false, 1).unwrap();
+        assert!(!event.back_to_back);
+        assert_eq!(event.points, 800);
+    }
+
+    #[test]
+    fn spin_without_lines_keeps_back_to_back() {
+        let mut scoring = Scoring::new();
+        scoring.lock(4, Spin::None, false, 1);
+        scoring.lock(0, Spin::Full, false, 1);
+        let event = scoring.lock(2, Spin::Full, false, 1).unwrap();
+        assert!(event.back_to_back);
+        assert_eq!(event.points, 1800);
+    }
+
+    #[test]
+    fn combos_add_fifty_per_step() {
+        let mut scoring = Scoring::new();
+        let combo: Vec<u64> = (0..4).map(|_| points(&mut scoring, 1, Spin::None, 1)).collect();
+        assert_eq!(combo, [100, 150, 200, 250]);
+
+        // A lock with no lines ends the combo
+        assert_eq!(points(&mut scoring, 0, Spin::None, 1), 0);
+        assert_eq!(points(&mut scoring, 1, Spin::None, 2), 200);
+    }
+
+    #[test]
+    fn perfect_clears_add_a_bonus() {
+        let mut scoring = Scoring::new();
+        let event = scoring.lock(4, Spin::None, true, 1).unwrap();
+        assert!(event.perfect_clear);
+        assert_eq!(event.points, 800 + 2000);
+
+        scoring.lock(0, Spin::None, false, 1);
+        let event = scoring.lock(4, Spin::None, true, 1).unwrap();
+        assert_eq!(event.points, 1200 + 3200);
+
+        assert_eq!(Scoring::new().lock(1, Spin::None, true, 2).unwrap().points, 200 + 1600);
+    }
+
+    #[test]
+    fn drops_score_per_cell() {
+        let mut scoring = Scoring::new();
+        scoring.drop(DropKind::Soft, 5);
+        scoring.drop(DropKind::Hard, 10);
+        assert_eq!(scoring.score, 25);
+    }
+}
@@ -0,0 +1,132 @@
+//! Player settings, read from environment variables (or the `.env` file).
+
+use crate::board::VISIBLE_HEIGHT;
+use crate::modes::marathon::LevelGoal;
+use crate::modes::sprint::SPRINT_LENGTHS;
+use crate::modes::ModeKind;
+use crate::randomizer::RandomizerKind;
+use log::warn;
+use std::env;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_PREVIEWS: usize = 6;
+
+#[derive(Clone)]
+pub struct Settings {
+    /// Game mode highlighted in the mode select, `MODE`
+    pub mode: ModeKind,
+    /// Which piece generator to use, `RANDOMIZER`
+    pub randomizer: RandomizerKind,
+    /// Seed for the piece generator, `SEED`. Every game picks one from the clock when not set.
+    pub seed: Option<u64>,
+    /// Number of upcoming pieces shown in the next queue, `PREVIEWS`, between 1 and 6
+    pub preview_count: usize,
+    /// Whether to draw the ghost piece, `GHOST`
+    pub ghost: bool,
+    /// Delayed auto shift, ticks a direction is held before it repeats, `DAS`
+    pub das: u32,
+    /// Auto repeat rate, ticks between repeats, 0 moves straight to the wall, `ARR`
+    pub arr: u32,
+    /// How many times faster than gravity soft drop is, `SDF`
+    pub soft_drop_factor: u32,
+    /// Ticks between a piece locking and the next one spawning, `ENTRY_DELAY`
+    pub entry_delay: u32,
+    /// Level a marathon starts at, `START_LEVEL`
+    pub start_level: u32,
+    /// Lines needed per level in marathon, `LEVEL_GOAL`, either `fixed` or `variable`
+    pub level_goal: LevelGoal,
+    /// Keep playing marathon past the final level, `ENDLESS`
+    pub endless: bool,
+    /// Lines to clear in sprint, `SPRINT_LINES`, one of 20, 40 or 100
+    pub sprint_lines: u32,
+    /// Rows of garbage dig mode starts with, `DIG_ROWS`
+    pub dig_rows: usize,
+    /// Percent chance the hole moves between garbage rows, `DIG_MESSINESS`
+    pub dig_messiness: u32,
+    /// Level file to load the dig garbage from instead of generating it, `DIG_LEVEL`
+    pub dig_level: Option<String>,
+    /// Level file the puzzle mode plays, picked on the puzzle select screen, `PUZZLE`
+    pub puzzle: Option<String>,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        let seed = env::var("SEED").ok().and_then(|value| match value.trim().parse() {
+            Ok(seed) => Some(seed),
+            Err(_) => {
+                warn!("Ignoring invalid value '{}' for SEED", value);
+                None
+            }
+        });
+
+        let mut sprint_lines = env_or("SPRINT_LINES", || 40);
+        if !SPRINT_LENGTHS.contains(&sprint_lines) {
+            warn!("Sprint length must be one of {:?}, using 40", SPRINT_LENGTHS);
+            sprint_lines = 40;
+        }
+
+        Self {
+            mode: env_or("MODE", || ModeKind::Marathon),
+            randomizer: env_or("RANDOMIZER", || RandomizerKind::Bag),
+            seed,
+            preview_count: env_or("PREVIEWS", || 5).clamp(1, MAX_PREVIEWS),
+            ghost: env_or("GHOST", || true),
+            das: env_or("DAS", || 10),
+            arr: env_or("ARR", || 2),
+            soft_drop_factor: env_or("SDF", || 20),
+            entry_delay: env_or("ENTRY_DELAY", || 0),
+            start_level: env_or("START_LEVEL", || 1),
+            level_goal: env_or("LEVEL_GOAL", || LevelGoal::Fixed),
+            endless: env_or("ENDLESS", || false),
+            sprint_lines,
+            dig_rows: env_or("DIG_ROWS", || 10).min(VISIBLE_HEIGHT - 2),
+            dig_messiness: env_or("DIG_MESSINESS", || 100).min(100),
+            dig_level: env::var("DIG_LEVEL").ok().filter(|path| !path.trim().is_empty()),
+            puzzle: env::var("PUZZLE").ok().filter(|path| !path.trim().is_empty()),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mode: ModeKind::Marathon,
+            randomizer: RandomizerKind::Bag,
+            seed: None,
+            preview_count: 5,
+            ghost: true,
+            das: 10,
+            arr: 2,
+            soft_drop_factor: 20,
+            entry_delay: 0,
+            start_level: 1,
+            level_goal: LevelGoal::Fixed,
+            endless: false,
+            sprint_lines: 40,
+            dig_rows: 10,
+            dig_messiness: 100,
+            dig_level: None,
+            puzzle: None,
+        }
+    }
+}
+
+/// A seed taken from the system clock, for games that don't need to be reproducible
+pub fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Parse the environment variable `key`, falling back to `default` if it is missing or invalid
+fn env_or<T: FromStr>(key: &str, default: impl FnOnce() -> T) -> T {
+    match env::var(key) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            warn!("Ignoring invalid value '{}' for {}", value, key);
+            default()
+        }),
+        Err(_) => default(),
+    }
+}
@@ -0,0 +1,182 @@
+//! Super Rotation System wall kicks.
+//!
+//! Kick offsets are written the way the guideline tables are, with y pointing up,
+//! and are flipped to the board's y-down coordinates when applied.
+
+use crate::board::Board;
+use crate::piece::{ActivePiece, PieceKind, Rotation};
+
+type Kicks = &'static [(i32, i32)];
+
+// J, L, S, T and Z share a table
+const JLSTZ_0_R: Kicks = &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)];
+const JLSTZ_R_0: Kicks = &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)];
+const JLSTZ_R_2: Kicks = &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)];
+const JLSTZ_2_R: Kicks = &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)];
+const JLSTZ_2_L: Kicks = &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)];
+const JLSTZ_L_2: Kicks = &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)];
+const JLSTZ_L_0: Kicks = &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)];
+const JLSTZ_0_L: Kicks = &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)];
+
+const I_0_R: Kicks = &[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)];
+const I_R_0: Kicks = &[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)];
+const I_R_2: Kicks = &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)];
+const I_2_R: Kicks = &[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)];
+const I_2_L: Kicks = &[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)];
+const I_L_2: Kicks = &[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)];
+const I_L_0: Kicks = &[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)];
+const I_0_L: Kicks = &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)];
+
+// SRS itself has no 180 degree rotation, these follow the widely used SRS+ table
+const HALF_0_2: Kicks = &[(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)];
+const HALF_2_0: Kicks = &[(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)];
+const HALF_R_L: Kicks = &[(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)];
+const HALF_L_R: Kicks = &[(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)];
+
+// The O piece never moves when it rotates
+const NO_KICKS: Kicks = &[(0, 0)];
+
+/// The offsets to try, in order, when `kind` rotates from `from` to `to`
+pub fn kicks(kind: PieceKind, from: Rotation, to: Rotation) -> Kicks {
+    use Rotation::*;
+
+    if kind == PieceKind::O {
+        return NO_KICKS;
+    }
+    match (from, to) {
+        (Spawn, Reverse) => HALF_0_2,
+        (Reverse, Spawn) => HALF_2_0,
+        (Right, Left) => HALF_R_L,
+        (Left, Right) => HALF_L_R,
+        _ if kind == PieceKind::I => match (from, to) {
+            (Spawn, Right) => I_0_R,
+            (Right, Spawn) => I_R_0,
+            (Right, Reverse) => I_R_2,
+            (Reverse, Right) => I_2_R,
+            (Reverse, Left) => I_2_L,
+            (Left, Reverse) => I_L_2,
+            (Left, Spawn) => I_L_0,
+            (Spawn, Left) => I_0_L,
+            _ => NO_KICKS,
+        },
+        (Spawn, Right) => JLSTZ_0_R,
+        (Right, Spawn) => JLSTZ_R_0,
+        (Right, Reverse) => JLSTZ_R_2,
+        (Reverse, Right) => JLSTZ_2_R,
+        (Reverse, Left) => JLSTZ_2_L,
+        (Left, Reverse) => JLSTZ_L_2,
+        (Left, Spawn) => JLSTZ_L_0,
+        (Spawn, Left) => JLSTZ_0_L,
+        _ => NO_KICKS,
+    }
+}
+
+/// Rotate `piece` to `target`, trying each kick in turn until one fits on the board.
+///
+/// Returns the rotated piece and the index of the kick that was used, or `None` if every kick was blocked.
+pub fn rotate(board: &Board, piece: &ActivePiece, target: Rotation) -> Option<(ActivePiece, usize)> {
+    kicks(piece.kind, piece.rotation, target)
+        .iter()
+        .enumerate()
+        .map(|(i, &(dx, dy))| (piece.rotated(target).shifted(dx, -dy), i))
+        .find(|(kicked, _)| board.fits(kicked.board_cells()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BOARD_HEIGHT, BOARD_WIDTH};
+
+    fn piece_at(kind: PieceKind, rotation: Rotation, x: i32, y: i32) -> ActivePiece {
+        ActivePiece {
+            kind,
+            rotation,
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn rotates_in_place_when_unobstructed() {
+        let board = Board::new();
+        let piece = piece_at(PieceKind::T, Rotation::Spawn, 3, 20);
+
+        let (rotated, kick) = rotate(&board, &piece, Rotation::Right).unwrap();
+        assert_eq!(kick, 0);
+        assert_eq!((rotated.x, rotated.y), (3, 20));
+        assert_eq!(rotated.rotation, Rotation::Right);
+    }
+
+    #[test]
+    fn o_piece_never_kicks() {
+        let board = Board::new();
+        let piece = piece_at(PieceKind::O, Rotation::Spawn, 0, 20);
+
+        assert_eq!(kicks(PieceKind::O, Rotation::Spawn, Rotation::Right).len(), 1);
+        let (rotated, _) = rotate(&board, &piece, Rotation::Right).unwrap();
+        assert!(rotated.board_cells().eq(piece.board_cells()));
+    }
+
+    #[test]
+    fn i_piece_kicks_off_the_right_wall() {
+        let board = Board::new();
+        // Vertical I against the right wall, its cells are in column 9
+        let piece = piece_at(PieceKind::I, Rotation::Right, BOARD_WIDTH as i32 - 3, 20);
+
+        let (rotated, kick) = rotate(&board, &piece, Rotation::Reverse).unwrap();
+        assert_eq!(kick, 1);
+        assert_eq!(rotated.x, BOARD_WIDTH as i32 - 4);
+    }
+
+    #[test]
+    fn t_piece_floor_kicks_up() {
+        let board = Board::new();
+        // Flat T lying on the floor, pointing down from there would poke through it
+        let piece = piece_at(PieceKind::T, Rotation::Reverse, 3, BOARD_HEIGHT as i32 - 2);
+        let floored = piece_at(PieceKind::T, Rotation::Spawn, 3, BOARD_HEIGHT as i32 - 2);
+        assert!(!board.fits(piece.board_cells()));
+        assert!(board.fits(floored.board_cells()));
+
+        let (rotated, kick) = rotate(&board, &floored, Rotation::Reverse).unwrap();
+        assert_eq!(kick, 1);
+        assert_eq!(rotated.y, BOARD_HEIGHT as i32 - 3);
+    }
+
+    #[test]
+    fn t_spin_triple_uses_the_last_kick() {
+        // The T sits under the overhang at column 3 and twists down into the slot
+        let mut board = Board::from_map_string(
+            "0001111111
+             0000111111
+             1110111111
+             1100111111
+             1110111111",
+        );
+        let piece = piece_at(PieceKind::T, Rotation::Spawn, 1, BOARD_HEIGHT as i32 - 5);
+        assert!(board.fits(piece.board_cells()));
+
+        let (rotated, kick) = rotate(&board, &piece, Rotation::Left).unwrap();
+        assert_eq!(kick, 4);
+        assert_eq!((rotated.x, rotated.y), (2, BOARD_HEIGHT as i32 - 3));
+
+        board.lock(rotated.board_cells(), rotated.tile_id());
+        assert_eq!(board.clear_lines(), 3);
+    }
+
+    #[test]
+    fn blocked_rotation_fails() {
+        // A flat I in a one row tunnel has nowhere to stand up
+        let board = Board::from_map_string(
+            "1111111111
+             0000000000
+             1111111111
+             1111111111
+             1111111111",
+        );
+        let piece = piece_at(PieceKind::I, Rotation::Spawn, 3, BOARD_HEIGHT as i32 - 5);
+        assert!(board.fits(piece.board_cells()));
+
+        assert!(rotate(&board, &piece, Rotation::Right).is_none());
+        assert!(rotate(&board, &piece, Rotation::Left).is_none());
+    }
+}
@@ -0,0 +1,557 @@
+//! Import levels drawn in the Tiled map editor, from `.tmx` (XML) or `.tmj` (JSON) maps.
+//!
+//! Every visible tile layer is laid over the ones before it. A tile's id in our tile set is taken from a
+//! `tile` property on the Tiled tile (a number or a name, the same as a level legend), falling back to its
+//! index in the tileset. A `color` property defines a new tile with that colour, like a legend colour.
+//! The map properties `name`, `author`, `pieces`, `hold` and `goal` work like the level file header.
+
+use crate::board::{BOARD_HEIGHT, BOARD_WIDTH};
+use crate::level::{parse_tile, Goal, Level};
+use crate::piece::{PieceKind, TILE_COUNT};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The top four bits of a GID flag flipped and rotated tiles, including hexagonal rotation, the rest is the tile
+const GID_MASK: u32 = 0x0fff_ffff;
+
+#[derive(Debug)]
+pub enum TiledError {
+    /// A map or tileset file could not be read
+    Io(io::Error),
+    /// The XML or JSON itself is malformed
+    Syntax(String),
+    /// Something the importer needs is missing or has the wrong type
+    Missing(String),
+    /// Layer data that can't be decoded, or an encoding or compression we don't support
+    BadData(String),
+    /// The map is not `BOARD_WIDTH` wide or is taller than the well
+    BadDimensions { width: usize, height: usize },
+    /// A tile the map uses that doesn't correspond to any of our tiles
+    UnknownGid(u32),
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TiledError::Io(err) => write!(f, "could not read the file: {err}"),
+            TiledError::Syntax(message) => write!(f, "malformed map: {message}"),
+            TiledError::Missing(what) => write!(f, "missing or invalid {what}"),
+            TiledError::BadData(message) => write!(f, "bad layer data: {message}"),
+            TiledError::BadDimensions { width, height } => write!(
+                f,
+                "map is {width}x{height}, it must be {BOARD_WIDTH} wide and at most {BOARD_HEIGHT} tall"
+            ),
+            TiledError::UnknownGid(gid) => write!(f, "tile with GID {gid} doesn't map to any tile"),
+        }
+    }
+}
+
+impl Error for TiledError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TiledError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TiledError {
+    fn from(err: io::Error) -> Self {
+        TiledError::Io(err)
+    }
+}
+
+/// What one Tiled tile stands for in our tile set
+#[derive(Copy, Clone, Default)]
+struct TileInfo {
+    id: Option<u8>,
+    color: Option<[u8; 4]>,
+}
+
+struct Tileset {
+    first_gid: u32,
+    /// Tiles with properties, by their index in the tileset
+    tiles: HashMap<u32, TileInfo>,
+}
+
+/// Everything read from a map before it is turned into a `Level`
+#[derive(Default)]
+struct Map {
+    width: usize,
+    height: usize,
+    properties: HashMap<String, String>,
+    tilesets: Vec<Tileset>,
+    /// Visible tile layers, bottom first, as row-major GIDs
+    layers: Vec<Vec<u32>>,
+}
+
+/// True if `path` is a map this module can import
+pub fn is_map(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "tmx" || extension == "tmj")
+}
+
+/// Import the Tiled map at `path`
+pub fn load(path: &Path) -> Result<Level, TiledError> {
+    let text = fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let map = if path.extension().is_some_and(|extension| extension == "tmj") {
+        read_json_map(&text, dir)?
+    } else {
+        read_xml_map(&text, dir)?
+    };
+    let mut level = map.into_level()?;
+    if level.name.is_empty() {
+        level.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    }
+    Ok(level)
+}
+
+impl Map {
+    fn into_level(self) -> Result<Level, TiledError> {
+        if self.width != BOARD_WIDTH || self.height == 0 || self.height > BOARD_HEIGHT {
+            return Err(TiledError::BadDimensions {
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        let mut level = Level::empty();
+        for (key, value) in &self.properties {
+            let invalid = || TiledError::Missing(format!("map property '{key}'"));
+            match key.as_str() {
+                "name" => level.name = value.clone(),
+                "author" => level.author = value.clone(),
+                "pieces" => {
+                    level.pieces = value
+                        .chars()
+                        .filter(|c| !c.is_whitespace())
+                        .map(PieceKind::from_char)
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?
+                }
+                "hold" => level.holds = Some(value.parse().map_err(|_| invalid())?),
+                "goal" => level.goal = Some(Goal::parse(value).ok_or_else(invalid)?),
+                _ => {}
+            }
+        }
+
+        // Maps are bottom aligned in the well, the same as level files
+        let top = BOARD_HEIGHT - self.height;
+        let mut ids: HashMap<u32, u8> = HashMap::new();
+        for layer in &self.layers {
+            for (i, &gid) in layer.iter().enumerate() {
+                let gid = gid & GID_MASK;
+                if gid == 0 {
+                    continue;
+                }
+                let id = match ids.get(&gid) {
+                    Some(&id) => id,
+                    None => {
+                        let id = self.tile_id(gid, &mut level)?;
+                        ids.insert(gid, id);
+                        id
+                    }
+                };
+                level.board.set(i % self.width, top + i / self.width, id);
+            }
+        }
+        Ok(level)
+    }
+
+    /// Our tile id for `gid`, defining a new tile in `level` if the Tiled tile gives a colour
+    fn tile_id(&self, gid: u32, level: &mut Level) -> Result<u8, TiledError> {
+        let tileset = self
+            .tilesets
+            .iter()
+            .filter(|tileset| tileset.first_gid <= gid)
+            .max_by_key(|tileset| tileset.first_gid)
+            .ok_or(TiledError::UnknownGid(gid))?;
+        let index = gid - tileset.first_gid;
+        let info = tileset.tiles.get(&index).copied().unwrap_or_default();
+
+        let id = match info.id {
+            Some(id) => id,
+            None => u8::try_from(index).map_err(|_| TiledError::UnknownGid(gid))?,
+        };
+        match info.color {
+            Some(_) if id < TILE_COUNT => {
+                let what = format!("colour for tile {id}, only new tiles from {TILE_COUNT} up have one");
+                return Err(TiledError::Missing(what));
+            }
+            Some(color) => level.tiles.push((id, color)),
+            None if id >= TILE_COUNT && level.tiles.iter().all(|&(defined, _)| defined != id) => {
+                return Err(TiledError::UnknownGid(gid));
+            }
+            None => {}
+        }
+        Ok(id)
+    }
+}
+
+/// Tile info from a Tiled tile's `tile` and `color` properties
+fn tile_info(properties: &HashMap<String, String>) -> Result<TileInfo, TiledError> {
+    let id = match properties.get("tile") {
+        Some(tile) => Some(parse_tile(tile).ok_or_else(|| TiledError::Missing(format!("tile '{tile}'")))?),
+        None => None,
+    };
+    let color = match properties.get("color") {
+        Some(color) => Some(parse_color(color).ok_or_else(|| TiledError::Missing(format!("colour '{color}'")))?),
+        None => None,
+    };
+    Ok(TileInfo { id, color })
+}
+
+/// Tiled writes colours as `#rrggbb` or `#aarrggbb`
+fn parse_color(text: &str) -> Option<[u8; 4]> {
+    let hex = text.strip_prefix('#')?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match hex.len() {
+        6 => Some([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Some([channel(2)?, channel(4)?, channel(6)?, channel(0)?]),
+        _ => None,
+    }
+}
+
+/// Decode base64 layer data, decompressing it if needed, into little-endian GIDs
+fn decode_base64(data: &str, compression: Option<&str>) -> Result<Vec<u32>, TiledError> {
+    let bytes = STANDARD
+        .decode(data.trim())
+        .map_err(|err| TiledError::BadData(format!("invalid base64: {err}")))?;
+    let bytes = match compression {
+        None | Some("") => bytes,
+        Some("zlib") => decompress(ZlibDecoder::new(&bytes[..]))?,
+        Some("gzip") => decompress(GzDecoder::new(&bytes[..]))?,
+        Some(other) => return Err(TiledError::BadData(format!("unsupported compression '{other}'"))),
+    };
+    if bytes.len() % 4 != 0 {
+        return Err(TiledError::BadData("data is not a whole number of tiles".to_string()));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+        .collect())
+}
+
+fn decompress(mut decoder: impl Read) -> Result<Vec<u8>, TiledError> {
+    let mut bytes = Vec::new();
+    decoder
+        .read_to_end(&mut bytes)
+        .map_err(|err| TiledError::BadData(format!("decompression failed: {err}")))?;
+    Ok(bytes)
+}
+
+fn decode_csv(data: &str) -> Result<Vec<u32>, TiledError> {
+    data.split(',')
+        .map(str::trim)
+        .filter(|gid| !gid.is_empty())
+        .map(|gid| gid.parse().map_err(|_| TiledError::BadData(format!("invalid GID '{gid}'"))))
+        .collect()
+}
+
+/// Check a layer holds exactly one GID per cell of the map
+fn check_layer(gids: Vec<u32>, map: &Map, name: &str) -> Result<Vec<u32>, TiledError> {
+    if gids.len() != map.width * map.height {
+        return Err(TiledError::BadData(format!(
+            "layer '{name}' has {} tiles, expected {}",
+            gids.len(),
+            map.width * map.height
+        )));
+    }
+    Ok(gids)
+}
+
+fn read_xml_map(text: &str, dir: &Path) -> Result<Map, TiledError> {
+    let document = roxmltree::Document::parse(text).map_err(|err| TiledError::Syntax(err.to_string()))?;
+    let root = document.root_element();
+    if !root.has_tag_name("map") {
+        return Err(TiledError::Missing("<map> element".to_string()));
+    }
+    if root.attribute("infinite") == Some("1") {
+        return Err(TiledError::BadData("infinite maps are not supported".to_string()));
+    }
+
+    let mut map = Map {
+        width: xml_number(root, "width")? as usize,
+        height: xml_number(root, "height")? as usize,
+        properties: xml_properties(root),
+        ..Map::default()
+    };
+    for tileset in root.children().filter(|node| node.has_tag_name("tileset")) {
+        let first_gid = xml_number(tileset, "firstgid")?;
+        let tiles = match tileset.attribute("source") {
+            Some(source) => read_tileset_file(&dir.join(source))?,
+            None => xml_tileset_tiles(tileset)?,
+        };
+        map.tilesets.push(Tileset { first_gid, tiles });
+    }
+    xml_layers(root, &mut map)?;
+    Ok(map)
+}
+
+/// Collect the visible tile layers under `parent`, descending into layer groups
+fn xml_layers(parent: roxmltree::Node, map: &mut Map) -> Result<(), TiledError> {
+    for node in parent.children().filter(|node| node.is_element()) {
+        if node.attribute("visible") == Some("0") {
+            continue;
+        }
+        match node.tag_name().name() {
+            "group" => xml_layers(node, map)?,
+            "layer" => {
+                let name = node.attribute("name").unwrap_or_default();
+                let data = node
+                    .children()
+                    .find(|child| child.has_tag_name("data"))
+                    .ok_or_else(|| TiledError::Missing(format!("<data> in layer '{name}'")))?;
+                let text = data.text().unwrap_or_default();
+                let gids = match data.attribute("encoding") {
+                    Some("csv") => decode_csv(text)?,
+                    Some("base64") => decode_base64(text, data.attribute("compression"))?,
+                    Some(other) => return Err(TiledError::BadData(format!("unsupported encoding '{other}'"))),
+                    None => data
+                        .children()
+                        .filter(|child| child.has_tag_name("tile"))
+                        .map(|tile| tile.attribute("gid").map_or(Ok(0), |gid| gid.parse()))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| TiledError::BadData(format!("invalid GID in layer '{name}'")))?,
+                };
+                map.layers.push(check_layer(gids, map, name)?);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn xml_number(node: roxmltree::Node, attribute: &str) -> Result<u32, TiledError> {
+    node.attribute(attribute)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| TiledError::Missing(format!("{attribute} attribute on <{}>", node.tag_name().name())))
+}
+
+/// The `<properties>` directly under `node`, as names and values
+fn xml_properties(node: roxmltree::Node) -> HashMap<String, String> {
+    node.children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|properties| properties.children().filter(|child| child.has_tag_name("property")))
+        .filter_map(|property| {
+            let value = property.attribute("value").or_else(|| property.text())?;
+            Some((property.attribute("name")?.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn xml_tileset_tiles(tileset: roxmltree::Node) -> Result<HashMap<u32, TileInfo>, TiledError> {
+    tileset
+        .children()
+        .filter(|node| node.has_tag_name("tile"))
+        .map(|tile| Ok((xml_number(tile, "id")?, tile_info(&xml_properties(tile))?)))
+        .collect()
+}
+
+/// Read an external tileset, either `.tsx` (XML) or `.tsj` (JSON)
+fn read_tileset_file(path: &Path) -> Result<HashMap<u32, TileInfo>, TiledError> {
+    let text = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|extension| extension == "tsj" || extension == "json") {
+        let tileset: Value = serde_json::from_str(&text).map_err(|err| TiledError::Syntax(err.to_string()))?;
+        json_tileset_tiles(&tileset)
+    } else {
+        let document = roxmltree::Document::parse(&text).map_err(|err| TiledError::Syntax(err.to_string()))?;
+        xml_tileset_tiles(document.root_element())
+    }
+}
+
+fn read_json_map(text: &str, dir: &Path) -> Result<Map, TiledError> {
+    let root: Value = serde_json::from_str(text).map_err(|err| TiledError::Syntax(err.to_string()))?;
+    if root["infinite"].as_bool() == Some(true) {
+        return Err(TiledError::BadData("infinite maps are not supported".to_string()));
+    }
+
+    let mut map = Map {
+        width: json_number(&root, "width")? as usize,
+        height: json_number(&root, "height")? as usize,
+        properties: json_properties(&root),
+        ..Map::default()
+    };
+    for tileset in root["tilesets"].as_array().into_iter().flatten() {
+        let first_gid = json_number(tileset, "firstgid")?;
+        let tiles = match tileset["source"].as_str() {
+            Some(source) => read_tileset_file(&dir.join(source))?,
+            None => json_tileset_tiles(tileset)?,
+        };
+        map.tilesets.push(Tileset { first_gid, tiles });
+    }
+    json_layers(&root, &mut map)?;
+    Ok(map)
+}
+
+/// Collect the visible tile layers under `parent`, descending into layer groups
+fn json_layers(parent: &Value, map: &mut Map) -> Result<(), TiledError> {
+    for layer in parent["layers"].as_array().into_iter().flatten() {
+        if layer["visible"].as_bool() == Some(false) {
+            continue;
+        }
+        match layer["type"].as_str() {
+            Some("group") => json_layers(layer, map)?,
+            Some("tilelayer") => {
+                let name = layer["name"].as_str().unwrap_or_default();
+                let gids = match (&layer["data"], layer["encoding"].as_str()) {
+                    (Value::Array(gids), _) => gids
+                        .iter()
+                        .map(|gid| gid.as_u64().and_then(|gid| u32::try_from(gid).ok()))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| TiledError::BadData(format!("invalid GID in layer '{name}'")))?,
+                    (Value::String(data), Some("base64")) => decode_base64(data, layer["compression"].as_str())?,
+                    _ => return Err(TiledError::Missing(format!("data in layer '{name}'"))),
+                };
+                map.layers.push(check_layer(gids, map, name)?);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn json_number(value: &Value, key: &str) -> Result<u32, TiledError> {
+    value[key]
+        .as_u64()
+        .and_then(|number| u32::try_from(number).ok())
+        .ok_or_else(|| TiledError::Missing(format!("'{key}'")))
+}
+
+/// The `properties` array of `value`, as names and values
+fn json_properties(value: &Value) -> HashMap<String, String> {
+    value["properties"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|property| {
+            let value = match &property["value"] {
+                Value::String(text) => text.clone(),
+                Value::Null => return None,
+                other => other.to_string(),
+            };
+            Some((property["name"].as_str()?.to_string(), value))
+        })
+        .collect()
+}
+
+fn json_tileset_tiles(tileset: &Value) -> Result<HashMap<u32, TileInfo>, TiledError> {
+    tileset["tiles"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|tile| Ok((json_number(tile, "id")?, tile_info(&json_properties(tile))?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, EMPTY};
+    use crate::garbage::GARBAGE_TILE_ID;
+
+    const BOTTOM: i32 = BOARD_HEIGHT as i32 - 1;
+    const GRASS: u8 = 2;
+    const ORANGE: u8 = 12;
+
+    fn fixture(name: &str) -> Level {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiled").join(name);
+        match load(&path) {
+            Ok(level) => level,
+            Err(err) => panic!("{name}: {err}"),
+        }
+    }
+
+    fn row(board: &Board, y: i32) -> Vec<u8> {
+        (0..BOARD_WIDTH as i32).map(|x| board.get(x, y).unwrap()).collect()
+    }
+
+    #[test]
+    fn csv_layers_stack_in_order() {
+        let level = fixture("csv.tmx");
+        assert_eq!(level.name, "Tiled CSV");
+        assert_eq!(level.pieces, vec![PieceKind::I]);
+        assert_eq!(level.goal, Some(Goal::Lines(2)));
+
+        let mut garbage = vec![GARBAGE_TILE_ID; BOARD_WIDTH];
+        garbage[4] = EMPTY;
+        assert_eq!(row(&level.board, BOTTOM), garbage);
+        assert_eq!(row(&level.board, BOTTOM - 1), garbage);
+
+        let t = PieceKind::T.tile_id();
+        assert_eq!(row(&level.board, BOTTOM - 2), [EMPTY, t, t, t, EMPTY, EMPTY, EMPTY, EMPTY, EMPTY, EMPTY]);
+        // The hidden layer is left out
+        assert_eq!(row(&level.board, BOTTOM - 3), [EMPTY; BOARD_WIDTH]);
+    }
+
+    /// `base64.tmx` and `map.tmj` describe the same board
+    fn check_detailed_board(level: &Level) {
+        let mut garbage = vec![GARBAGE_TILE_ID; BOARD_WIDTH];
+        garbage[4] = ORANGE;
+        assert_eq!(row(&level.board, BOTTOM), garbage);
+        assert_eq!(row(&level.board, BOTTOM - 1), [GRASS; BOARD_WIDTH]);
+        assert_eq!(row(&level.board, BOTTOM - 3), [EMPTY; BOARD_WIDTH]);
+        assert_eq!(level.tiles, vec![(ORANGE, [255, 128, 0, 255])]);
+    }
+
+    #[test]
+    fn compressed_base64_with_an_external_tileset() {
+        let level = fixture("base64.tmx");
+        assert_eq!(level.name, "base64");
+        check_detailed_board(&level);
+    }
+
+    #[test]
+    fn json_maps_import_the_same() {
+        let level = fixture("map.tmj");
+        assert_eq!(level.name, "Tiled JSON");
+        assert_eq!(level.holds, Some(1));
+        check_detailed_board(&level);
+    }
+
+    fn xml_error(map: &str) -> TiledError {
+        let Err(err) = read_xml_map(map, Path::new("")).and_then(Map::into_level) else {
+            panic!("map imported without errors");
+        };
+        err
+    }
+
+    #[test]
+    fn bad_maps_are_rejected() {
+        let map = |width: usize, data: &str| {
+            format!(
+                r#"<map width="{width}" height="1"><tileset firstgid="1"/>
+                <layer name="a"><data encoding="csv">{data}</data></layer></map>"#
+            )
+        };
+        let ones = "1,".repeat(10);
+        assert!(matches!(xml_error(&map(8, &ones)), TiledError::BadData(_)));
+        assert!(matches!(xml_error(&map(8, "1,1,1,1,1,1,1,1")), TiledError::BadDimensions { width: 8, height: 1 }));
+        assert!(matches!(xml_error(&map(10, "1,1,1,1,1,1,1,1,1,200")), TiledError::UnknownGid(200)));
+        assert!(matches!(xml_error(&map(10, "1,1,1,1,1,1,1,1,1,x")), TiledError::BadData(_)));
+        assert!(matches!(xml_error("<map"), TiledError::Syntax(_)));
+    }
+
+    #[test]
+    fn flip_and_rotation_flags_are_ignored() {
+        let map = |data: &str| {
+            let map = format!(
+                r#"<map width="10" height="1"><tileset firstgid="1"/>
+                <layer name="a"><data encoding="csv">{data}</data></layer></map>"#
+            );
+            read_xml_map(&map, Path::new("")).and_then(Map::into_level).unwrap()
+        };
+        let plain = map("1,1,1,1,0,0,0,0,0,0");
+        // Horizontal, vertical and diagonal flips, and the hexagonal rotation
+        let flagged = map("2147483649,1073741825,536870913,268435457,0,0,0,0,0,0");
+        assert_eq!(row(&flagged.board, BOTTOM), row(&plain.board, BOTTOM));
+    }
+}
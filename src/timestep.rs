@@ -0,0 +1,91 @@
+//! Fixed timestep clock: the simulation advances in whole ticks of exactly the same length,
+//! however often the window happens to redraw or deliver events.
+
+use std::time::{Duration, Instant};
+
+/// Simulation ticks per second
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// Most ticks run to catch up after a stall, so a long pause doesn't freeze the game while it fast-forwards
+const MAX_CATCH_UP_TICKS: u32 = 8;
+
+pub struct FixedTimestep {
+    step: Duration,
+    /// Real time that has passed but not been simulated yet
+    accumulator: Duration,
+    last: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32) -> Self {
+        Self {
+            step: Duration::from_secs(1) / ticks_per_second,
+            accumulator: Duration::ZERO,
+            last: Instant::now(),
+        }
+    }
+
+    /// Account for the real time passed up to `now`. Returns the number of ticks to simulate.
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        self.advance_by(elapsed)
+    }
+
+    /// Account for `elapsed` of real time. Returns the number of ticks to simulate.
+    pub fn advance_by(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator = (self.accumulator + elapsed).min(self.step * MAX_CATCH_UP_TICKS);
+        let ticks = (self.accumulator.as_nanos() / self.step.as_nanos()) as u32;
+        self.accumulator -= self.step * ticks;
+        ticks
+    }
+
+    /// How far between the last tick and the next one we are, from 0 to 1.
+    ///
+    /// The renderer can use this to interpolate between the previous and current simulation state.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    /// When the next tick is due
+    pub fn next_tick(&self) -> Instant {
+        self.last + (self.step - self.accumulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_second_is_sixty_ticks() {
+        let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+        let ticks: u32 = (0..100).map(|_| timestep.advance_by(Duration::from_millis(10))).sum();
+        assert_eq!(ticks, 60);
+    }
+
+    #[test]
+    fn partial_steps_carry_over() {
+        let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+        assert_eq!(timestep.advance_by(Duration::from_millis(10)), 0);
+        assert_eq!(timestep.advance_by(Duration::from_millis(10)), 1);
+        // 3.33 ms left over, plus 30 ms makes two more
+        assert_eq!(timestep.advance_by(Duration::from_millis(30)), 2);
+    }
+
+    #[test]
+    fn alpha_is_the_fraction_of_a_tick_left_over() {
+        let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+        assert_eq!(timestep.alpha(), 0.0);
+        timestep.advance_by(Duration::from_millis(10));
+        assert!((timestep.alpha() - 0.6).abs() < 0.001);
+    }
+
+    #[test]
+    fn stalls_only_catch_up_a_few_ticks() {
+        let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+        assert_eq!(timestep.advance_by(Duration::from_secs(5)), MAX_CATCH_UP_TICKS);
+        // The rest of the stall is dropped rather than owed
+        assert_eq!(timestep.advance_by(Duration::ZERO), 0);
+    }
+}
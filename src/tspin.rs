@@ -0,0 +1,166 @@
+//! T-spin recognition with the 3-corner rule.
+
+use crate::board::Board;
+use crate::piece::{ActivePiece, PieceKind, Rotation, Turn};
+use crate::scoring::Spin;
+
+/// Index of the last JLSTZ kick, the long (±1, ±2) one
+const LAST_JLSTZ_KICK: usize = 4;
+
+/// The rotation a piece last made
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Kick {
+    pub turn: Turn,
+    /// Index into the SRS kick table of the kick that fit, 0 for rotating in place
+    pub index: usize,
+}
+
+/// Corners of the T's 3x3 bounding box
+const CORNERS: [(i32, i32); 4] = [(0, 0), (2, 0), (0, 2), (2, 2)];
+
+/// The two corners on the side the T is pointing at, for each rotation state
+fn front_corners(rotation: Rotation) -> [(i32, i32); 2] {
+    match rotation {
+        Rotation::Spawn => [(0, 0), (2, 0)],
+        Rotation::Right => [(2, 0), (2, 2)],
+        Rotation::Reverse => [(0, 2), (2, 2)],
+        Rotation::Left => [(0, 0), (0, 2)],
+    }
+}
+
+/// Decide whether `piece` locking here is a T-spin.
+///
+/// `last_kick` is the last rotation, or `None` if the piece has moved since it last rotated. At least three
+/// corners around the T must be filled; a full spin needs both front corners, unless a quarter turn used the
+/// last JLSTZ kick, the long (1, 2) one, which always counts as a full spin. The 180° tables have a (1, 2) kick
+/// too, but it doesn't upgrade.
+pub fn detect(board: &Board, piece: &ActivePiece, last_kick: Option<Kick>) -> Spin {
+    let Some(kick) = last_kick else {
+        return Spin::None;
+    };
+    if piece.kind != PieceKind::T {
+        return Spin::None;
+    }
+
+    let filled = |&(x, y): &(i32, i32)| board.is_occupied(piece.x + x, piece.y + y);
+    if CORNERS.iter().filter(|corner| filled(corner)).count() < 3 {
+        return Spin::None;
+    }
+
+    if front_corners(piece.rotation).iter().all(filled) || (kick.turn != Turn::Half && kick.index == LAST_JLSTZ_KICK) {
+        Spin::Full
+    } else {
+        Spin::Mini
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BOARD_HEIGHT;
+    use std::fs;
+
+    /// A rotation that needed no kick
+    const IN_PLACE: Option<Kick> = Some(Kick { turn: Turn::Clockwise, index: 0 });
+
+    /// Load a board fixture. Cells marked `T` are where the T piece is, the rest is read like a level.
+    fn load_fixture(name: &str) -> (Board, ActivePiece) {
+        let path = format!("{}/tests/fixtures/tspin/{}.data", env!("CARGO_MANIFEST_DIR"), name);
+        let text = fs::read_to_string(path).unwrap();
+        let board = Board::from_map_string(&text.replace('T', "0"));
+
+        // Rows are bottom aligned, the same as `Board::from_map_string`
+        let rows: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        let top = (BOARD_HEIGHT - rows.len()) as i32;
+        let mut cells: Vec<(i32, i32)> = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'T' {
+                    cells.push((x as i32, top + y as i32));
+                }
+            }
+        }
+        cells.sort();
+
+        for rotation in [Rotation::Spawn, Rotation::Right, Rotation::Reverse, Rotation::Left] {
+            for (dx, dy) in PieceKind::T.cells(rotation) {
+                let piece = ActivePiece {
+                    kind: PieceKind::T,
+                    rotation,
+                    x: cells[0].0 - dx,
+                    y: cells[0].1 - dy,
+                };
+                let mut placed: Vec<(i32, i32)> = piece.board_cells().collect();
+                placed.sort();
+                if placed == cells {
+                    assert!(board.fits(piece.board_cells()));
+                    return (board, piece);
+                }
+            }
+        }
+        panic!("fixture {name} has no T piece");
+    }
+
+    /// Lock the piece and return how many lines it cleared
+    fn lines_cleared(mut board: Board, piece: &ActivePiece) -> usize {
+        board.lock(piece.board_cells(), piece.tile_id());
+        board.clear_lines()
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let (board, piece) = load_fixture("double");
+        assert_eq!(detect(&board, &piece, IN_PLACE), Spin::Full);
+        assert_eq!(lines_cleared(board, &piece), 2);
+    }
+
+    #[test]
+    fn missing_front_corner_is_a_mini() {
+        let (board, piece) = load_fixture("mini");
+        assert_eq!(detect(&board, &piece, IN_PLACE), Spin::Mini);
+        assert_eq!(lines_cleared(board, &piece), 1);
+    }
+
+    #[test]
+    fn long_kick_upgrades_a_mini() {
+        let (board, piece) = load_fixture("fin_kick");
+        assert_eq!(detect(&board, &piece, IN_PLACE), Spin::Mini);
+        let long_kick = Kick { turn: Turn::Clockwise, index: LAST_JLSTZ_KICK };
+        assert_eq!(detect(&board, &piece, Some(long_kick)), Spin::Full);
+        // Not any other kick
+        assert_eq!(detect(&board, &piece, Some(Kick { index: 3, ..long_kick })), Spin::Mini);
+        assert_eq!(lines_cleared(board, &piece), 2);
+    }
+
+    #[test]
+    fn half_turn_kicks_never_upgrade() {
+        let (board, piece) = load_fixture("fin_kick");
+        // (1, 2) in the 180° table for R to L
+        let kick = Kick { turn: Turn::Half, index: 2 };
+        assert_eq!(crate::srs::kicks(PieceKind::T, Rotation::Right, Rotation::Left)[kick.index], (1, 2));
+        assert_eq!(detect(&board, &piece, Some(kick)), Spin::Mini);
+        assert_eq!(detect(&board, &piece, Some(Kick { index: LAST_JLSTZ_KICK, ..kick })), Spin::Mini);
+    }
+
+    #[test]
+    fn last_action_must_be_a_rotation() {
+        let (board, piece) = load_fixture("double");
+        assert_eq!(detect(&board, &piece, None), Spin::None);
+    }
+
+    #[test]
+    fn two_corners_is_not_a_spin() {
+        let (board, piece) = load_fixture("no_corners");
+        assert_eq!(detect(&board, &piece, IN_PLACE), Spin::None);
+    }
+
+    #[test]
+    fn only_t_pieces_spin() {
+        let (board, piece) = load_fixture("double");
+        let piece = ActivePiece {
+            kind: PieceKind::L,
+            ..piece
+        };
+        assert_eq!(detect(&board, &piece, IN_PLACE), Spin::None);
+    }
+}
@@ -0,0 +1,14 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" name="blocks" tilewidth="10" tileheight="10" tilecount="4" columns="4">
+ <tile id="1">
+  <properties>
+   <property name="tile" value="dirt"/>
+  </properties>
+ </tile>
+ <tile id="3">
+  <properties>
+   <property name="tile" value="12"/>
+   <property name="color" type="color" value="#ffff8000"/>
+  </properties>
+ </tile>
+</tileset>